@@ -1,72 +1,285 @@
-use std::collections::{hash_map::RandomState, HashMap};
+use std::collections::HashMap;
 
 use aoc_runner_derive::aoc;
-use num::Integer;
+use itertools::Itertools;
+
+use crate::utils::math::{crt, Congruence};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Instruction {
+    Left = 0,
+    Right = 1,
+}
+
+impl TryFrom<char> for Instruction {
+    type Error = char;
+
+    fn try_from(value: char) -> Result<Self, Self::Error> {
+        match value {
+            'L' => Ok(Instruction::Left),
+            'R' => Ok(Instruction::Right),
+            c => Err(c),
+        }
+    }
+}
+
+pub type NodeId = u32;
+
+/// Left/right instructions plus a network whose node labels are interned into
+/// dense ids, so that walking it never hashes a label.
+#[derive(Debug, Clone)]
+pub struct Network<'s> {
+    pub instructions: Vec<Instruction>,
+    labels: Vec<&'s str>,
+    ids: HashMap<&'s str, NodeId>,
+    edges: Vec<[NodeId; 2]>,
+}
+
+impl<'s> Network<'s> {
+    /// Parses the puzzle input. Labels may have any length.
+    pub fn parse(src: &'s str) -> Self {
+        let mut lines = src.lines();
+        let instructions = lines
+            .next()
+            .unwrap()
+            .chars()
+            .map(|c| Instruction::try_from(c).unwrap_or_else(|c| panic!("Bad instruction: {}", c)))
+            .collect();
+        let mut network = Self {
+            instructions,
+            labels: Vec::new(),
+            ids: HashMap::new(),
+            edges: Vec::new(),
+        };
+        let mut edges = Vec::new();
+        for line in lines.filter(|l| !l.is_empty()) {
+            let (node, paths) = line.split_once(" = ").unwrap();
+            let (left, right) = paths
+                .trim_start_matches('(')
+                .trim_end_matches(')')
+                .split_once(", ")
+                .unwrap();
+            let node = network.intern(node);
+            let paths = [network.intern(left), network.intern(right)];
+            edges.push((node, paths));
+        }
+        network.edges = vec![[NodeId::MAX; 2]; network.labels.len()];
+        for (node, paths) in edges {
+            network.edges[node as usize] = paths;
+        }
+        if let Some(node) = network.edges.iter().position(|e| e[0] == NodeId::MAX) {
+            panic!("Node {} is never defined", network.labels[node]);
+        }
+        network
+    }
+
+    fn intern(&mut self, label: &'s str) -> NodeId {
+        *self.ids.entry(label).or_insert_with(|| {
+            self.labels.push(label);
+            (self.labels.len() - 1) as NodeId
+        })
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.labels.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.labels.is_empty()
+    }
+
+    pub fn id(&self, label: &str) -> Option<NodeId> {
+        self.ids.get(label).copied()
+    }
+
+    #[inline]
+    pub fn label(&self, node: NodeId) -> &'s str {
+        self.labels[node as usize]
+    }
+
+    pub fn nodes(&self) -> impl Iterator<Item = NodeId> {
+        0..self.len() as NodeId
+    }
+
+    #[inline]
+    pub fn step(&self, node: NodeId, instruction: Instruction) -> NodeId {
+        self.edges[node as usize][instruction as usize]
+    }
+
+    /// Whether a ghost starts at `node`.
+    #[inline]
+    pub fn is_start(&self, node: NodeId) -> bool {
+        self.label(node).ends_with('A')
+    }
+
+    /// Whether a ghost may stop at `node`.
+    #[inline]
+    pub fn is_end(&self, node: NodeId) -> bool {
+        self.label(node).ends_with('Z')
+    }
+}
 
 #[aoc(day8, part1)]
 pub fn part1(input: &str) -> usize {
-    let mut lines = input.lines();
-    let mut instructions = lines.next().unwrap().chars().cycle();
-    lines.next().unwrap();
-    let network_map = lines
-        .map(|l| (&l[..3], (&l[7..10], &l[12..15])))
-        .collect::<HashMap<_, _, RandomState>>();
+    let network = Network::parse(input);
+    let mut instructions = network.instructions.iter().cycle();
+    let end = network.id("ZZZ").unwrap();
     let mut count = 0;
-    let mut current_node = "AAA";
-    while current_node != "ZZZ" {
+    let mut current_node = network.id("AAA").unwrap();
+    while current_node != end {
         count += 1;
-        let instruction = instructions.next().unwrap();
-        let paths = network_map.get(current_node).unwrap();
-        current_node = match instruction {
-            'L' => paths.0,
-            'R' => paths.1,
-            _ => unreachable!(),
-        }
+        current_node = network.step(current_node, *instructions.next().unwrap());
     }
     count
 }
 
 #[aoc(day8, part2)]
 pub fn part2(input: &str) -> usize {
-    let mut lines = input.lines();
-    let instructions = lines.next().unwrap().chars().cycle();
-    lines.next().unwrap();
-    let network_map = lines
-        .map(|l| (&l[..3], (&l[7..10], &l[12..15])))
-        .collect::<HashMap<_, _, RandomState>>();
-
-    let starting_nodes = network_map
-        .keys()
-        .filter(|n| n.ends_with('A'))
-        .copied()
+    let network = Network::parse(input);
+    let paths = network
+        .nodes()
+        .filter(|&n| network.is_start(n))
+        .map(|start| GhostPath::trace(&network, start))
         .collect::<Vec<_>>();
+    first_simultaneous_arrival(&paths).unwrap()
+}
+
+/// Every step count at which a single ghost stands on a `Z` node. Once the ghost
+/// repeats a (node, instruction) state, its arrivals repeat too.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GhostPath {
+    /// Steps taken before entering the cycle.
+    pub tail: usize,
+    /// Steps taken by one turn of the cycle.
+    pub cycle: usize,
+    /// Step counts below `tail + cycle` that end on a `Z` node, in ascending order.
+    pub hits: Vec<usize>,
+}
 
-    let loop_lengths = starting_nodes.iter().copied().map(|start| {
+impl GhostPath {
+    pub fn trace(network: &Network, start: NodeId) -> Self {
+        let instruction_count = network.instructions.len();
+        let mut seen = vec![None; network.len() * instruction_count];
+        let mut hits = Vec::new();
         let mut current = start;
         let mut count = 0;
-        let mut instructions = instructions.clone();
         loop {
-            count += 1;
-            let instruction = instructions.next().unwrap();
-            let paths = network_map.get(current).unwrap();
-            current = match instruction {
-                'L' => paths.0,
-                'R' => paths.1,
-                _ => unreachable!(),
-            };
-            if current.ends_with('Z') {
-                break count;
+            let instruction_index = count % instruction_count;
+            let state = current as usize * instruction_count + instruction_index;
+            if let Some(tail) = seen[state] {
+                return Self {
+                    tail,
+                    cycle: count - tail,
+                    hits,
+                };
+            }
+            seen[state] = Some(count);
+            if network.is_end(current) {
+                hits.push(count);
             }
+            current = network.step(current, network.instructions[instruction_index]);
+            count += 1;
         }
-    });
+    }
+
+    pub fn is_at_end(&self, steps: usize) -> bool {
+        let steps = if steps < self.tail {
+            steps
+        } else {
+            self.tail + (steps - self.tail) % self.cycle
+        };
+        self.hits.binary_search(&steps).is_ok()
+    }
 
-    loop_lengths.reduce(|acc, i| acc.lcm(&i)).unwrap()
+    fn cycle_hits(&self) -> impl Iterator<Item = usize> + Clone + '_ {
+        self.hits.iter().copied().filter(|&h| h >= self.tail)
+    }
+}
+
+/// Returns the first step count at which every ghost stands on a `Z` node, or
+/// `None` if they never do.
+pub fn first_simultaneous_arrival(paths: &[GhostPath]) -> Option<usize> {
+    if paths.is_empty() {
+        return None;
+    }
+    // Before every ghost has entered its cycle, try each step count directly.
+    let max_tail = paths.iter().map(|p| p.tail).max().unwrap();
+    if let Some(steps) = (0..max_tail).find(|&s| paths.iter().all(|p| p.is_at_end(s))) {
+        return Some(steps);
+    }
+    // Afterwards, each ghost arrives at one of its cycle hits plus a whole number
+    // of cycles, so try every combination of hits.
+    paths
+        .iter()
+        .map(GhostPath::cycle_hits)
+        .multi_cartesian_product()
+        .filter_map(|hits| {
+            crt(hits
+                .into_iter()
+                .zip(paths)
+                .map(|(h, p)| Congruence::new(h as i64, p.cycle as i64)))
+        })
+        .map(|c| c.first_at_or_after(max_tail as i64) as usize)
+        .min()
+}
+
+#[aoc(day8, part2, lcm)]
+pub fn part2_lcm(input: &str) -> usize {
+    let network = Network::parse(input);
+    let loop_lengths = network
+        .nodes()
+        .filter(|&n| network.is_start(n))
+        .map(|start| {
+            let mut current = start;
+            let mut count = 0;
+            let mut instructions = network.instructions.iter().cycle();
+            loop {
+                count += 1;
+                current = network.step(current, *instructions.next().unwrap());
+                if network.is_end(current) {
+                    break count;
+                }
+            }
+        });
+
+    // Every ghost is back at its `Z` node after a whole number of loops, so the
+    // first simultaneous arrival is the first positive common multiple.
+    crt(loop_lengths.map(|l| Congruence::new(0, l as i64)))
+        .unwrap()
+        .first_at_or_after(1) as usize
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    const SAMPLE_PART2: &str = r"LR
+
+11A = (11B, XXX)
+11B = (XXX, 11Z)
+11Z = (11B, XXX)
+22A = (22B, XXX)
+22B = (22C, 22C)
+22C = (22Z, 22Z)
+22Z = (22B, 22B)
+XXX = (XXX, XXX)";
+
+    #[test]
+    fn part1_sample() {
+        let input = r"RL
+
+AAA = (BBB, CCC)
+BBB = (DDD, EEE)
+CCC = (ZZZ, GGG)
+DDD = (DDD, DDD)
+EEE = (EEE, EEE)
+GGG = (GGG, GGG)
+ZZZ = (ZZZ, ZZZ)";
+        assert_eq!(part1(input), 2);
+    }
+
     #[test]
     fn part2_sample() {
         assert_eq!(
@@ -85,4 +298,77 @@ XXX = (XXX, XXX)"
             6
         );
     }
+
+    #[test]
+    fn part2_lcm_sample() {
+        assert_eq!(part2_lcm(SAMPLE_PART2), 6);
+    }
+
+    #[test]
+    fn part2_unaligned_cycles() {
+        // The first ghost reaches `11Z` after 1 step and then every 3 steps, while the
+        // second reaches `22Z` every 2 steps, so they first meet after 4 steps rather
+        // than lcm(1, 2) = 2.
+        let input = r"L
+
+11A = (11Z, XXX)
+11Z = (11B, XXX)
+11B = (11C, XXX)
+11C = (11Z, XXX)
+22A = (22B, XXX)
+22B = (22Z, XXX)
+22Z = (22B, XXX)
+XXX = (XXX, XXX)";
+        assert_eq!(part2(input), 4);
+        assert_ne!(part2_lcm(input), 4);
+    }
+
+    #[test]
+    fn trace_ghost_sample() {
+        let network = Network::parse(SAMPLE_PART2);
+        assert_eq!(
+            GhostPath::trace(&network, network.id("22A").unwrap()),
+            GhostPath {
+                tail: 1,
+                cycle: 6,
+                hits: vec![3, 6]
+            }
+        );
+    }
+
+    #[test]
+    fn network_long_labels() {
+        let network = Network::parse(
+            r"RL
+
+START = (MIDDLE, END)
+MIDDLE = (START, END)
+END = (END, END)",
+        );
+        assert_eq!(network.len(), 3);
+        let start = network.id("START").unwrap();
+        let middle = network.step(start, Instruction::Left);
+        assert_eq!(network.label(middle), "MIDDLE");
+        assert_eq!(
+            network.label(network.step(middle, Instruction::Right)),
+            "END"
+        );
+    }
+
+    #[test]
+    fn no_simultaneous_arrival() {
+        let input = r"L
+
+11A = (11Z, XXX)
+11Z = (11B, XXX)
+11B = (11Z, XXX)
+22A = (22B, XXX)
+22B = (22Z, XXX)
+22Z = (22B, XXX)
+XXX = (XXX, XXX)";
+        let network = Network::parse(input);
+        let paths =
+            ["11A", "22A"].map(|start| GhostPath::trace(&network, network.id(start).unwrap()));
+        assert_eq!(first_simultaneous_arrival(&paths), None);
+    }
 }
@@ -1,63 +1,58 @@
 use aoc_runner_derive::aoc;
-use num::Zero;
 
-#[aoc(day9, part1)]
-pub fn part1(input: &str) -> i32 {
-    let sequences = input.lines().map(|l| {
+use crate::utils::polynomial::NewtonPolynomial;
+
+fn parse_sequences(input: &str) -> impl Iterator<Item = Vec<i128>> + '_ {
+    input.lines().map(|l| {
         l.split_whitespace()
-            .map(str::parse::<i32>)
+            .map(str::parse::<i128>)
             .map(Result::unwrap)
-            .collect::<Vec<_>>()
-    });
+            .collect()
+    })
+}
 
-    sequences
-        .into_iter()
+#[aoc(day9, part1)]
+pub fn part1(input: &str) -> i128 {
+    parse_sequences(input)
         .map(|s| {
-            let mut derivatives = vec![s];
-            while let Some(base) = derivatives
-                .last()
-                .and_then(|s| (!s.iter().all(|i| i.is_zero())).then_some(s))
-            {
-                derivatives.push(
-                    (0..base.len() - 1)
-                        .into_iter()
-                        .map(|i| base[i + 1] - base[i])
-                        .collect(),
-                );
-            }
-            derivatives
-                .into_iter()
-                .rev()
-                .fold(0, |prev, d| d.last().unwrap() + prev)
+            let next = s.len() as i128;
+            NewtonPolynomial::fit(s).eval(next)
         })
         .sum()
 }
 
 #[aoc(day9, part2)]
-pub fn part2(input: &str) -> i32 {
-    let sequences = input.lines().map(|l| {
-        l.split_whitespace()
-            .map(str::parse::<i32>)
-            .map(Result::unwrap)
-            .collect::<Vec<_>>()
-    });
-
-    sequences
-        .into_iter()
-        .map(|s| {
-            let mut derivatives = vec![s];
-            while let Some(base) = derivatives
-                .last()
-                .and_then(|s| (!s.iter().all(|i| i.is_zero())).then_some(s))
-            {
-                derivatives.push(
-                    (0..base.len() - 1)
-                        .into_iter()
-                        .map(|i| base[i + 1] - base[i])
-                        .collect(),
-                );
-            }
-            derivatives.into_iter().rev().fold(0, |prev, d| d[0] - prev)
-        })
+pub fn part2(input: &str) -> i128 {
+    parse_sequences(input)
+        .map(|s| NewtonPolynomial::fit(s).eval(-1))
         .sum()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = r"0 3 6 9 12 15
+1 3 6 10 15 21
+10 13 16 21 30 45";
+
+    #[test]
+    fn part1_sample() {
+        assert_eq!(part1(SAMPLE), 114);
+    }
+
+    #[test]
+    fn part2_sample() {
+        assert_eq!(part2(SAMPLE), 2);
+    }
+
+    #[test]
+    fn extrapolate_far() {
+        // Forward differences at 0 are 10, 3, 0, 2, so this is 10 + 3x + 2 * (x choose 3).
+        let polynomial = NewtonPolynomial::fit([10, 13, 16, 21, 30, 45]);
+        assert_eq!(polynomial.degree(), Some(3));
+        assert_eq!(polynomial.eval(6), 68);
+        assert_eq!(polynomial.eval(-1), 5);
+        assert_eq!(polynomial.eval(1_000_000), 333332333337000010);
+    }
+}
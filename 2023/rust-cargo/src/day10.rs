@@ -1,159 +1,327 @@
+use std::fmt::Display;
+
 use aoc_runner_derive::{aoc, aoc_generator};
-use petgraph::{algo::dijkstra, graph::NodeIndex, visit::Dfs, Directed, Graph};
+use grid::Grid;
+use itertools::Itertools;
+
+use crate::utils::{
+    geometry::Polygon,
+    grid::{
+        bit_grid::BitGrid,
+        grid_translate, parse_grid_with_u8,
+        region::{enclosed_cells, Connectivity},
+        GridCoords, S4Direction, ALL_S4_DIRECTIONS,
+    },
+};
+
+/// A tile of the pipe maze. Each pipe's value is the mask of the directions it
+/// connects to, with bit `d as u8` set for every connected [`S4Direction`] `d`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Pipe {
+    #[default]
+    Ground = 0b0000,
+    Horizontal = 0b0101,
+    Vertical = 0b1010,
+    NorthEast = 0b0110,
+    NorthWest = 0b0011,
+    SouthWest = 0b1001,
+    SouthEast = 0b1100,
+}
+
+const ALL_PIPES: [Pipe; 6] = [
+    Pipe::Horizontal,
+    Pipe::Vertical,
+    Pipe::NorthEast,
+    Pipe::NorthWest,
+    Pipe::SouthWest,
+    Pipe::SouthEast,
+];
+
+impl Pipe {
+    #[inline]
+    pub fn connections(self) -> u8 {
+        self as u8
+    }
+
+    #[inline]
+    pub fn connects(self, direction: S4Direction) -> bool {
+        self.connections() & (1 << direction as u8) != 0
+    }
+
+    pub fn from_connections(mask: u8) -> Option<Self> {
+        ALL_PIPES.into_iter().find(|p| p.connections() == mask)
+    }
+
+    /// Direction a walker leaves this pipe in, after entering it while heading
+    /// towards `heading`.
+    pub fn exit(self, heading: S4Direction) -> Option<S4Direction> {
+        let entry = heading.reverse();
+        if !self.connects(entry) {
+            return None;
+        }
+        ALL_S4_DIRECTIONS
+            .into_iter()
+            .find(|&d| d != entry && self.connects(d))
+    }
+
+    pub fn box_drawing(self) -> char {
+        use Pipe::*;
+        match self {
+            Ground => ' ',
+            Horizontal => '─',
+            Vertical => '│',
+            NorthEast => '└',
+            NorthWest => '┘',
+            SouthWest => '┐',
+            SouthEast => '┌',
+        }
+    }
+}
+
+impl TryFrom<u8> for Pipe {
+    type Error = u8;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        use Pipe::*;
+        Ok(match value {
+            b'.' => Ground,
+            b'-' => Horizontal,
+            b'|' => Vertical,
+            b'L' => NorthEast,
+            b'J' => NorthWest,
+            b'7' => SouthWest,
+            b'F' => SouthEast,
+            c => return Err(c),
+        })
+    }
+}
 
+impl Display for Pipe {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use Pipe::*;
+        let c = match self {
+            Ground => '.',
+            Horizontal => '-',
+            Vertical => '|',
+            NorthEast => 'L',
+            NorthWest => 'J',
+            SouthWest => '7',
+            SouthEast => 'F',
+        };
+        write!(f, "{}", c)
+    }
+}
+
+/// The maze, with the start tile replaced by the pipe that closes the loop.
 pub struct Input {
-    width: usize,
-    height: usize,
-    start: NodeIndex<usize>,
-    graph: Graph<(), (), Directed, usize>,
-    matrix: Vec<char>,
+    tiles: Grid<Pipe>,
+    start: GridCoords,
 }
 
 #[aoc_generator(day10)]
 pub fn generator(input: &str) -> Input {
-    let width = input.lines().next().unwrap().len();
-    let height = input.lines().count();
-    let matrix: Vec<char> = input
-        .lines()
-        .flat_map(str::lines)
-        .flat_map(str::chars)
-        .collect();
-    let start = matrix
-        .iter()
-        .copied()
-        .position(|c| c == 'S')
-        .unwrap()
-        .into();
-    let get_adjacent = |i: usize, (j, k): (isize, isize)| {
-        i.checked_add_signed(j * (width as isize) + k)
-            .and_then(|i| (i < matrix.len()).then_some(i))
-    };
-    let mut graph = Graph::from_edges(
-        matrix
-            .iter()
-            .copied()
-            .enumerate()
-            .filter(|(_, c)| !['S', '.'].contains(c))
-            .map(|(i, c)| {
-                (
-                    i,
-                    match c {
-                        '|' => [(-1, 0), (1, 0)],
-                        '-' => [(0, -1), (0, 1)],
-                        'L' => [(-1, 0), (0, 1)],
-                        'J' => [(-1, 0), (0, -1)],
-                        '7' => [(0, -1), (1, 0)],
-                        'F' => [(0, 1), (1, 0)],
-                        c => unreachable!("{}", c),
-                    },
-                )
-            })
-            .map(|(i, n)| (i, n.map(|n| get_adjacent(i, n))))
-            .flat_map(|(i, n)| n.into_iter().flat_map(move |n| n.map(|n| (i, n)))),
-    );
-    let start_neighbors = graph.neighbors_undirected(start).collect::<Vec<_>>();
-    for target in start_neighbors {
-        graph.add_edge(start, target, ());
-    }
-    Input {
-        width,
-        height,
-        start,
-        graph,
-        matrix,
+    let mut start = None;
+    let mut tiles = parse_grid_with_u8(input, |coords, c| {
+        if c == b'S' {
+            start = Some(coords);
+            Pipe::Ground
+        } else {
+            Pipe::try_from(c).unwrap_or_else(|c| panic!("Unexpected tile: {}", c as char))
+        }
+    });
+    let start = start.expect("Missing start tile");
+    infer_start(&mut tiles, start).expect("No loop passes through the start tile");
+    Input { tiles, start }
+}
+
+/// Finds the pipe under the start tile, one connecting two neighbors that lead
+/// back to the start, and places it there. Neighbors beyond the edge of the maze
+/// are never connected.
+fn infer_start(tiles: &mut Grid<Pipe>, start: GridCoords) -> Option<Pipe> {
+    let dimensions = (tiles.rows(), tiles.cols());
+    let candidates = ALL_S4_DIRECTIONS
+        .into_iter()
+        .filter(|&d| {
+            grid_translate(start, d.vector(1), &dimensions)
+                .map(|n| tiles[n].connects(d.reverse()))
+                .unwrap_or(false)
+        })
+        .collect_vec();
+    for (a, b) in candidates.into_iter().tuple_combinations() {
+        let pipe = Pipe::from_connections((1 << a as u8) | (1 << b as u8))?;
+        tiles[start] = pipe;
+        if walk_loop(tiles, start).is_some() {
+            return Some(pipe);
+        }
+    }
+    tiles[start] = Pipe::Ground;
+    None
+}
+
+/// Walks the pipes from `start` until getting back to it, returning the tiles
+/// visited in order, or `None` if the path breaks off.
+fn walk_loop(tiles: &Grid<Pipe>, start: GridCoords) -> Option<Vec<GridCoords>> {
+    let dimensions = (tiles.rows(), tiles.cols());
+    let mut heading = ALL_S4_DIRECTIONS
+        .into_iter()
+        .find(|&d| tiles[start].connects(d))?;
+    let mut coords = start;
+    let mut path = vec![start];
+    loop {
+        coords = grid_translate(coords, heading.vector(1), &dimensions)?;
+        heading = tiles[coords].exit(heading)?;
+        if coords == start {
+            return Some(path);
+        }
+        path.push(coords);
+    }
+}
+
+impl Input {
+    /// Returns the tiles of the loop, in the order they are walked from the start.
+    pub fn pipe_loop(&self) -> Vec<GridCoords> {
+        walk_loop(&self.tiles, self.start).unwrap()
     }
 }
 
 #[aoc(day10, part1)]
 pub fn part1(input: &Input) -> usize {
-    let distances = dijkstra(&input.graph, input.start, None, |_| 1);
-    *distances.values().max().unwrap()
+    input.pipe_loop().len() / 2
 }
 
-const WEST_ADJACENT_NODES: [char; 3] = ['-', 'J', '7'];
-const EAST_ADJACENT_NODES: [char; 3] = ['-', 'F', 'L'];
-const SOUTH_ADJACENT_NODES: [char; 3] = ['|', '7', 'F'];
-const NORTH_ADJACENT_NODES: [char; 3] = ['|', 'J', 'L'];
+/// Returns the maze with every tile outside the loop replaced by ground.
+fn clean_loop(input: &Input) -> Grid<Pipe> {
+    let mut on_loop = BitGrid::new(input.tiles.rows(), input.tiles.cols());
+    for coords in input.pipe_loop() {
+        on_loop.set(coords, true);
+    }
+    let mut clean_matrix = input.tiles.clone();
+    for (i, j) in (0..clean_matrix.rows()).cartesian_product(0..clean_matrix.cols()) {
+        if !on_loop[(i, j)] {
+            clean_matrix[(i, j)] = Pipe::Ground;
+        }
+    }
+    clean_matrix
+}
 
 #[aoc(day10, part2)]
-pub fn part2(
-    Input {
-        width,
-        height,
-        start,
-        graph,
-        matrix,
-    }: &Input,
-) -> usize {
-    let width = *width;
-    let height = *height;
-    let start = *start;
-    let mut point_traverse = Dfs::new(&graph, start);
-    let mut loop_nodes = Vec::new();
-    while let Some(point) = point_traverse.next(&graph) {
-        loop_nodes.push(point.index());
-    }
-    let mut clean_matrix = matrix.clone();
-    for (_, c) in clean_matrix
-        .iter_mut()
-        .enumerate()
-        .filter(|(i, _)| !loop_nodes.contains(i))
-    {
-        *c = '.';
-    }
-    let start = start.index();
-    let neighboring_nodes = (
-        clean_matrix[start - 1],
-        clean_matrix[start - width],
-        clean_matrix[start + 1],
-        clean_matrix[start + width],
-    ); // WNES
-    clean_matrix[start] = match (
-        EAST_ADJACENT_NODES.contains(&clean_matrix[start - 1]),
-        SOUTH_ADJACENT_NODES.contains(&clean_matrix[start - width]),
-        WEST_ADJACENT_NODES.contains(&clean_matrix[start + 1]),
-        NORTH_ADJACENT_NODES.contains(&clean_matrix[start + width]),
-    ) {
-        (false, true, false, true) => '|',
-        (true, false, true, false) => '-',
-        (false, true, true, false) => 'L',
-        (true, true, false, false) => 'J',
-        (true, false, false, true) => '7',
-        (false, false, true, true) => 'F',
-        p => unreachable!("{:?} {:?}", neighboring_nodes, p),
-    };
-    let mut area_inside = 0;
-    for y in 0..height {
-        let row_start = y * width;
-        for x in 0..width {
-            let index = row_start + x;
-            let is_inside = if clean_matrix[index] == '.' {
-                let mut count = 0;
-                let mut edge_start = '.';
-                for i in row_start..=row_start + x {
-                    let c = clean_matrix[i];
-                    match (c, edge_start) {
-                        ('|', _) => count += 1,
-                        ('F', _) => edge_start = 'F',
-                        ('L', _) => edge_start = 'L',
-                        ('J', 'F') => count += 1,
-                        ('J', 'L') => count += 2,
-                        ('7', 'F') => count += 2,
-                        ('7', 'L') => count += 1,
-                        ('.' | '-', _) => {}
-                        _ => unreachable!("{}, {}", c, edge_start),
-                    }
+pub fn part2(input: &Input) -> usize {
+    let vertices = input
+        .pipe_loop()
+        .into_iter()
+        .map(|(i, j)| (i as isize, j as isize))
+        .collect();
+    Polygon::from_vertices(vertices).interior_points()
+}
+
+/// Where a tile lies relative to the loop.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    Loop,
+    Inside,
+    #[default]
+    Outside,
+}
+
+/// Classifies every tile of a cleaned maze, scanning each row from the left.
+fn sides(clean_matrix: &Grid<Pipe>) -> Grid<Side> {
+    use Pipe::*;
+    let mut sides = Grid::new(clean_matrix.rows(), clean_matrix.cols());
+    for (i, row) in clean_matrix.iter_rows().enumerate() {
+        // Crossing `|`, `F-*J` or `L-*7` flips between outside and inside, while
+        // `F-*7` and `L-*J` only graze the loop.
+        let mut is_inside = false;
+        let mut edge_start = Ground;
+        for (j, &pipe) in row.enumerate() {
+            match (pipe, edge_start) {
+                (Vertical, _) | (NorthWest, SouthEast) | (SouthWest, NorthEast) => {
+                    is_inside = !is_inside
                 }
-                count % 2 == 1
-            } else {
-                false
+                (SouthEast | NorthEast, _) => edge_start = pipe,
+                _ => {}
+            }
+            sides[(i, j)] = match (pipe, is_inside) {
+                (Ground, true) => Side::Inside,
+                (Ground, false) => Side::Outside,
+                _ => Side::Loop,
             };
-            print!("{}", if is_inside { 'X' } else { clean_matrix[index] });
-            area_inside += is_inside as usize;
         }
-        println!();
     }
-    area_inside
+    sides
+}
+
+#[aoc(day10, part2, scanline)]
+pub fn part2_scanline(input: &Input) -> usize {
+    sides(&clean_loop(input))
+        .iter()
+        .filter(|&&s| s == Side::Inside)
+        .count()
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct RenderOptions {
+    /// Whether to colour the output with ANSI escape codes.
+    pub colour: bool,
+}
+
+const ANSI_RESET: &str = "\x1b[0m";
+const ANSI_START: &str = "\x1b[1;33m";
+const ANSI_INSIDE: &str = "\x1b[32m";
+const ANSI_OUTSIDE: &str = "\x1b[2m";
+
+/// Draws the loop with box-drawing characters, marking enclosed tiles with `I`
+/// and the rest with `O`, one line per row.
+pub fn render_loop(input: &Input, RenderOptions { colour }: RenderOptions) -> String {
+    let clean_matrix = clean_loop(input);
+    let sides = sides(&clean_matrix);
+    let mut output = String::new();
+    for (i, row) in clean_matrix.iter_rows().enumerate() {
+        for (j, &pipe) in row.enumerate() {
+            let (glyph, style) = match sides[(i, j)] {
+                Side::Loop if (i, j) == input.start => (pipe.box_drawing(), ANSI_START),
+                Side::Loop => (pipe.box_drawing(), ""),
+                Side::Inside => ('I', ANSI_INSIDE),
+                Side::Outside => ('O', ANSI_OUTSIDE),
+            };
+            if colour && !style.is_empty() {
+                output.push_str(style);
+                output.push(glyph);
+                output.push_str(ANSI_RESET);
+            } else {
+                output.push(glyph);
+            }
+        }
+        output.push('\n');
+    }
+    output
+}
+
+#[aoc(day10, part2, flood_fill)]
+pub fn part2_flood_fill(input: &Input) -> usize {
+    // Each tile is drawn as a 3x3 block, so that the outside can squeeze between
+    // adjacent pipes.
+    let clean_matrix = clean_loop(input);
+    let mut boundary = Grid::new(3 * clean_matrix.rows(), 3 * clean_matrix.cols());
+    for ((i, j), &pipe) in clean_matrix.indexed_iter() {
+        if pipe == Pipe::Ground {
+            continue;
+        }
+        let center = (3 * i + 1, 3 * j + 1);
+        boundary[center] = true;
+        for arm in ALL_S4_DIRECTIONS.into_iter().filter(|&d| pipe.connects(d)) {
+            let (di, dj) = arm.vector(1);
+            boundary[(
+                center.0.wrapping_add_signed(di),
+                center.1.wrapping_add_signed(dj),
+            )] = true;
+        }
+    }
+    let enclosed = enclosed_cells(&boundary, Connectivity::Four);
+    (0..clean_matrix.rows())
+        .cartesian_product(0..clean_matrix.cols())
+        .filter(|&(i, j)| enclosed[(3 * i + 1, 3 * j + 1)])
+        .count()
 }
 
 #[cfg(test)]
@@ -176,4 +344,133 @@ L|7||
 L|-JF";
         assert_eq!(part1(&generator(input)), 4);
     }
+
+    #[test]
+    fn part1_sample2() {
+        let input = r"..F7.
+.FJ|.
+SJ.L7
+|F--J
+LJ...";
+        assert_eq!(part1(&generator(input)), 8);
+    }
+
+    #[test]
+    fn start_shape_inference() {
+        let input = generator(
+            r"S-7
+|.|
+L-J",
+        );
+        assert_eq!(input.tiles[(0, 0)], Pipe::SouthEast);
+        assert_eq!(
+            input.pipe_loop(),
+            vec![
+                (0, 0),
+                (0, 1),
+                (0, 2),
+                (1, 2),
+                (2, 2),
+                (2, 1),
+                (2, 0),
+                (1, 0)
+            ]
+        );
+    }
+
+    #[test]
+    fn part2_sample() {
+        let input = r"...........
+.S-------7.
+.|F-----7|.
+.||.....||.
+.||.....||.
+.|L-7.F-J|.
+.|..|.|..|.
+.L--J.L--J.
+...........";
+        assert_eq!(part2(&generator(input)), 4);
+        assert_eq!(part2_scanline(&generator(input)), 4);
+        assert_eq!(part2_flood_fill(&generator(input)), 4);
+
+        let input = r".F----7F7F7F7F-7....
+.|F--7||||||||FJ....
+.||.FJ||||||||L7....
+FJL7L7LJLJ||LJ.L-7..
+L--J.L7...LJS7F-7L7.
+....F-J..F7FJ|L7L7L7
+....L7.F7||L7|.L7L7|
+.....|FJLJ|FJ|F7|.LJ
+....FJL-7.||.||||...
+....L---J.LJ.LJLJ...";
+        assert_eq!(part2(&generator(input)), 8);
+        assert_eq!(part2_scanline(&generator(input)), 8);
+        assert_eq!(part2_flood_fill(&generator(input)), 8);
+
+        let input = r"FF7FSF7F7F7F7F7F---7
+L|LJ||||||||||||F--J
+FL-7LJLJ||||||LJL-77
+F--JF--7||LJLJ7F7FJ-
+L---JF-JLJ.||-FJLJJ7
+|F|F-JF---7F7-L7L|7|
+|FFJF7L7F-JF7|JL---7
+7-L-JL7||F7|L7F-7F7|
+L.L7LFJ|||||FJL7||LJ
+L7JLJL-JLJLJL--JLJ.L";
+        assert_eq!(part2(&generator(input)), 10);
+        assert_eq!(part2_scanline(&generator(input)), 10);
+        assert_eq!(part2_flood_fill(&generator(input)), 10);
+    }
+
+    #[test]
+    fn render_loop_sample() {
+        let input = generator(
+            r"...........
+.S-------7.
+.|F-----7|.
+.||.....||.
+.||.....||.
+.|L-7.F-J|.
+.|..|.|..|.
+.L--J.L--J.
+...........",
+        );
+        assert_eq!(
+            render_loop(&input, RenderOptions::default()),
+            "\
+OOOOOOOOOOO
+O┌───────┐O
+O│┌─────┐│O
+O││OOOOO││O
+O││OOOOO││O
+O│└─┐O┌─┘│O
+O│II│O│II│O
+O└──┘O└──┘O
+OOOOOOOOOOO
+"
+        );
+    }
+
+    #[test]
+    fn render_loop_colour_sample() {
+        let input = generator(
+            r".....
+.S-7.
+.|.|.
+.L-J.
+.....",
+        );
+        let dim_row = "\x1b[2mO\x1b[0m".repeat(5);
+        let expected = [
+            dim_row.clone(),
+            "\x1b[2mO\x1b[0m\x1b[1;33m┌\x1b[0m─┐\x1b[2mO\x1b[0m".to_string(),
+            "\x1b[2mO\x1b[0m│\x1b[32mI\x1b[0m│\x1b[2mO\x1b[0m".to_string(),
+            "\x1b[2mO\x1b[0m└─┘\x1b[2mO\x1b[0m".to_string(),
+            dim_row,
+        ];
+        assert_eq!(
+            render_loop(&input, RenderOptions { colour: true }),
+            expected.map(|l| l + "\n").concat()
+        );
+    }
 }
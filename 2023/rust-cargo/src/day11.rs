@@ -16,52 +16,73 @@ pub fn generator(input: &str) -> Vec<Point> {
 }
 
 #[aoc(day11, part1)]
-pub fn part1(points: &Vec<Point>) -> usize {
-    let mut points = points.clone();
-    expand_universe(&mut points, 2);
-    (0..points.len())
-        .tuple_combinations()
-        .map(|(i, j)| manhattan_distance(&points[i], &points[j]))
-        .sum()
+pub fn part1(points: &[Point]) -> usize {
+    sum_of_distances(&expand(points, 2))
 }
 
 #[aoc(day11, part2)]
-pub fn part2(points: &Vec<Point>) -> usize {
-    let mut points = points.clone();
-    expand_universe(&mut points, 1000000);
-    sum_of_distances(&points)
+pub fn part2(points: &[Point]) -> usize {
+    sum_of_distances(&expand(points, 1000000))
 }
 
-fn expand_universe(points: &mut [Point], times: usize) {
-    let times = times - 1;
-    points.sort_by_key(|(_, y)| *y);
-    let mut previous_y = 0;
-    let mut empty_rows = 0;
-    for (_, y) in points.iter_mut() {
-        let y_diff = *y - previous_y;
-        previous_y = *y;
-        empty_rows += y_diff.saturating_sub(1);
-        *y += empty_rows * times;
-    }
-    points.sort_by_key(|(x, _)| *x);
-    let mut previous_x = 0;
-    let mut empty_columns = 0;
-    for (x, _) in points.iter_mut() {
-        let x_diff = *x - previous_x;
-        previous_x = *x;
-        empty_columns += x_diff.saturating_sub(1);
-        *x += empty_columns * times;
-    }
+/// Returns the galaxies after every empty row and column is replaced by
+/// `factor` empty ones, in the same order as `points`.
+pub fn expand(points: &[Point], factor: usize) -> Vec<Point> {
+    assert!(factor > 0, "expansion factor must be positive");
+    let xs = expand_axis(points.iter().map(|&(x, _)| x), factor);
+    let ys = expand_axis(points.iter().map(|&(_, y)| y), factor);
+    xs.into_iter().zip(ys).collect()
 }
 
-fn sum_of_distances(points: &[Point]) -> usize {
-    (0..points.len())
-        .tuple_combinations()
-        .map(|(i, j)| manhattan_distance(&points[i], &points[j]))
-        .sum()
+fn expand_axis<I: Iterator<Item = usize> + Clone>(coords: I, factor: usize) -> Vec<usize> {
+    let occupied = coords.clone().sorted_unstable().dedup().collect_vec();
+    coords
+        .map(|c| {
+            let empty_before = c - occupied.partition_point(|&o| o < c);
+            c + empty_before * (factor - 1)
+        })
+        .collect()
 }
 
-fn manhattan_distance(a: &(usize, usize), b: &(usize, usize)) -> usize {
+/// Distance between galaxies `a` and `b`, indexed in reading order, once the
+/// universe has expanded by `factor`.
+pub fn galaxy_distance(points: &[Point], a: usize, b: usize, factor: usize) -> usize {
+    assert!(factor > 0, "expansion factor must be positive");
+    let axis_distance = |ca: usize, cb: usize, coords: &dyn Fn(&Point) -> usize| {
+        let (lo, hi) = (ca.min(cb), ca.max(cb));
+        let occupied_between = points
+            .iter()
+            .map(coords)
+            .filter(|&c| lo < c && c < hi)
+            .unique()
+            .count();
+        let empty_between = (hi - lo).saturating_sub(1) - occupied_between;
+        hi - lo + empty_between * (factor - 1)
+    };
+    let (xa, ya) = points[a];
+    let (xb, yb) = points[b];
+    axis_distance(xa, xb, &|p| p.0) + axis_distance(ya, yb, &|p| p.1)
+}
+
+/// Sum of the Manhattan distances between every pair of points.
+pub fn sum_of_distances(points: &[Point]) -> usize {
+    sum_of_axis_distances(points.iter().map(|&(x, _)| x))
+        + sum_of_axis_distances(points.iter().map(|&(_, y)| y))
+}
+
+/// Once sorted, each coordinate is at least as large as all the previous ones,
+/// so its distances to them add up to `c * i - (sum of the previous ones)`.
+fn sum_of_axis_distances<I: Iterator<Item = usize>>(coords: I) -> usize {
+    coords
+        .sorted_unstable()
+        .enumerate()
+        .fold((0, 0), |(sum, prefix), (i, c)| {
+            (sum + c * i - prefix, prefix + c)
+        })
+        .0
+}
+
+pub fn manhattan_distance(a: &Point, b: &Point) -> usize {
     a.0.abs_diff(b.0) + a.1.abs_diff(b.1)
 }
 
@@ -69,6 +90,17 @@ fn manhattan_distance(a: &(usize, usize), b: &(usize, usize)) -> usize {
 mod test {
     use super::*;
 
+    const SAMPLE: &str = r"...#......
+.......#..
+#.........
+..........
+......#...
+.#........
+.........#
+..........
+.......#..
+#...#.....";
+
     #[test]
     pub fn part1_sample() {
         let input = r"...#......
@@ -98,16 +130,24 @@ mod test {
             (4, 9),
         ];
 
-        let mut points = sample_points.clone();
-        expand_universe(&mut points, 2);
-        assert_eq!(sum_of_distances(&points), 374);
-
-        let mut points = sample_points.clone();
-        expand_universe(&mut points, 10);
-        assert_eq!(sum_of_distances(&points), 1030);
+        assert_eq!(sum_of_distances(&expand(&sample_points, 2)), 374);
+        assert_eq!(sum_of_distances(&expand(&sample_points, 10)), 1030);
+        assert_eq!(sum_of_distances(&expand(&sample_points, 100)), 8410);
+    }
 
-        let mut points = sample_points.clone();
-        expand_universe(&mut points, 100);
-        assert_eq!(sum_of_distances(&points), 8410);
+    #[test]
+    pub fn galaxy_distance_sample() {
+        let points = generator(SAMPLE);
+        assert_eq!(galaxy_distance(&points, 4, 8, 2), 9);
+        assert_eq!(galaxy_distance(&points, 0, 6, 2), 15);
+        assert_eq!(galaxy_distance(&points, 2, 5, 2), 17);
+        assert_eq!(galaxy_distance(&points, 7, 8, 2), 5);
+        let expanded = expand(&points, 100);
+        for (a, b) in (0..points.len()).tuple_combinations() {
+            assert_eq!(
+                galaxy_distance(&points, a, b, 100),
+                manhattan_distance(&expanded[a], &expanded[b])
+            );
+        }
     }
 }
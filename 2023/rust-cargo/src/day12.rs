@@ -1,4 +1,7 @@
-use std::iter::repeat;
+use std::{
+    fmt::Write,
+    iter::{once, repeat},
+};
 
 use aoc_runner_derive::aoc;
 use itertools::Itertools;
@@ -7,50 +10,106 @@ const DAMAGED_CHAR: char = '#';
 const UNKNOWN_CHAR: char = '?';
 
 #[aoc(day12, part1)]
-pub fn part1(input: &str) -> usize {
-    input
-        .lines()
-        .map(|l| {
-            let mut iter = l.split_whitespace();
-            (
-                iter.next().unwrap().chars().collect::<Vec<_>>(),
-                iter.next()
-                    .unwrap()
-                    .split(',')
-                    .map(str::parse)
-                    .map(Result::unwrap)
-                    .collect::<Vec<_>>(),
-            )
-        })
-        .map(|(row, groups)| MonogramSolver::new(row, groups).solve())
-        .sum()
+pub fn part1(input: &str) -> u128 {
+    total_arrangements(input, 1, UNKNOWN_CHAR)
 }
 
 #[aoc(day12, part2)]
-pub fn part2(input: &str) -> usize {
+pub fn part2(input: &str) -> u128 {
+    total_arrangements(input, 5, UNKNOWN_CHAR)
+}
+
+/// Sums the arrangements of every record once unfolded `factor` times, with
+/// copies of each row joined by `separator`.
+pub fn total_arrangements(input: &str, factor: usize, separator: char) -> u128 {
     input
         .lines()
-        .map(|l| {
-            let mut iter = l.split_whitespace();
-            let row = iter.next().unwrap().chars();
-            let groups = iter
+        .map(|l| Record::parse(l).unfold(factor, separator).arrangements())
+        .sum()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Record {
+    pub row: Vec<char>,
+    pub groups: Vec<usize>,
+}
+
+impl Record {
+    pub fn parse(line: &str) -> Self {
+        let mut iter = line.split_whitespace();
+        Self {
+            row: iter.next().unwrap().chars().collect(),
+            groups: iter
                 .next()
                 .unwrap()
                 .split(',')
                 .map(str::parse)
-                .map(Result::unwrap);
-
-            (
-                repeat(row.clone().chain(std::iter::once(UNKNOWN_CHAR)))
-                    .take(4)
-                    .flatten()
-                    .chain(row)
-                    .collect::<Vec<_>>(),
-                repeat(groups).take(5).flatten().collect::<Vec<_>>(),
+                .map(Result::unwrap)
+                .collect(),
+        }
+    }
+
+    pub fn unfold(&self, factor: usize, separator: char) -> Self {
+        assert!(factor > 0, "unfold factor must be positive");
+        Self {
+            row: repeat(self.row.iter().chain(once(&separator)))
+                .take(factor - 1)
+                .flatten()
+                .chain(&self.row)
+                .copied()
+                .collect(),
+            groups: repeat(&self.groups)
+                .take(factor)
+                .flatten()
+                .copied()
+                .collect(),
+        }
+    }
+
+    pub fn arrangements(&self) -> u128 {
+        self.solver().solve().0
+    }
+
+    /// Renders the table filled in while counting arrangements: the cell for
+    /// group `g` at column `i` holds the number of ways to place groups `g..`
+    /// with group `g` starting at `i`, and `-` marks states never reached.
+    pub fn explain(&self) -> String {
+        let (total, memo) = self.solver().solve();
+        let cells = memo
+            .chunks(self.row.len().max(1))
+            .map(|line| {
+                line.iter()
+                    .map(|c| c.map_or_else(|| "-".to_string(), |n| n.to_string()))
+                    .collect_vec()
+            })
+            .collect_vec();
+        let width = cells.iter().flatten().map(String::len).max().unwrap_or(1);
+        let labels = self.groups.iter().map(usize::to_string).collect_vec();
+        let label_width = labels.iter().map(String::len).max().unwrap_or(0);
+
+        let mut out = String::new();
+        writeln!(
+            out,
+            "{:label_width$} {}",
+            "",
+            self.row.iter().map(|c| format!("{c:>width$}")).join(" ")
+        )
+        .unwrap();
+        for (label, line) in labels.iter().zip(&cells) {
+            writeln!(
+                out,
+                "{label:>label_width$} {}",
+                line.iter().map(|c| format!("{c:>width$}")).join(" ")
             )
-        })
-        .map(|(row, groups)| MonogramSolver::new(row, groups).solve())
-        .sum()
+            .unwrap();
+        }
+        writeln!(out, "total: {total}").unwrap();
+        out
+    }
+
+    fn solver(&self) -> MonogramSolver {
+        MonogramSolver::new(self.row.clone(), self.groups.clone())
+    }
 }
 
 struct MonogramSolver {
@@ -63,22 +122,29 @@ impl MonogramSolver {
         Self { row, groups }
     }
 
-    pub fn solve(&self) -> usize {
+    /// Returns the number of arrangements along with the memo table, indexed
+    /// by `position + group_id * row.len()`.
+    pub fn solve(&self) -> (u128, Vec<Option<u128>>) {
         let mut memo = vec![None; self.row.len() * self.groups.len()];
-        self.row
+        if self.groups.is_empty() {
+            return ((!self.row.contains(&DAMAGED_CHAR)) as u128, memo);
+        }
+        let total = self
+            .row
             .iter()
             .take_while_inclusive(|c| **c != DAMAGED_CHAR)
             .enumerate()
             .map(|(i, _)| self.memoised_recursive(i, 0, &mut memo))
-            .sum()
+            .sum();
+        (total, memo)
     }
 
     fn memoised_recursive(
         &self,
         position: usize,
         group_id: usize,
-        memo: &mut [Option<usize>],
-    ) -> usize {
+        memo: &mut [Option<u128>],
+    ) -> u128 {
         let id = position + group_id * self.row.len();
         if let Some(r) = memo[id] {
             r
@@ -89,7 +155,7 @@ impl MonogramSolver {
         }
     }
 
-    fn recursive(&self, start: usize, group_id: usize, memo: &mut [Option<usize>]) -> usize {
+    fn recursive(&self, start: usize, group_id: usize, memo: &mut [Option<u128>]) -> u128 {
         let end = start + self.groups[group_id];
         let row = self.row.as_slice();
 
@@ -146,4 +212,40 @@ mod tests {
 ?###???????? 3,2,1";
         assert_eq!(part2(input), 525152);
     }
+
+    #[test]
+    fn unfold_parameters() {
+        let record = Record::parse(".# 1");
+        assert_eq!(
+            record.unfold(5, UNKNOWN_CHAR),
+            Record::parse(".#?.#?.#?.#?.# 1,1,1,1,1")
+        );
+        assert_eq!(record.unfold(1, UNKNOWN_CHAR), record);
+        assert_eq!(
+            record.unfold(3, OPERATIONAL_CHAR).row,
+            ".#..#..#".chars().collect_vec()
+        );
+
+        let input = "???.### 1,1,3";
+        assert_eq!(total_arrangements(input, 1, UNKNOWN_CHAR), 1);
+        assert_eq!(total_arrangements(input, 5, UNKNOWN_CHAR), 1);
+        assert_eq!(total_arrangements(input, 2, DAMAGED_CHAR), 0);
+        let input = "?###???????? 3,2,1";
+        assert_eq!(total_arrangements(input, 5, UNKNOWN_CHAR), 506250);
+        assert_eq!(
+            total_arrangements(input, 20, UNKNOWN_CHAR),
+            10u128 * 15u128.pow(19)
+        );
+    }
+
+    #[test]
+    fn explain_sample() {
+        let expected = "  ? ? ? . # # #
+1 1 0 0 0 0 - -
+1 - - 1 0 0 - -
+3 - - - - 1 - -
+total: 1
+";
+        assert_eq!(Record::parse("???.### 1,1,3").explain(), expected);
+    }
 }
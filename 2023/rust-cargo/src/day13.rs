@@ -1,121 +1,128 @@
 use aoc_runner_derive::{aoc, aoc_generator};
 
-use crate::utils::vec::matrix_transpose;
-
+/// Pattern of ash and rocks, stored as one bitmask per row and per column
+/// where rocks are set bits. Bit `j` of a row is column `j`, and bit `i` of a
+/// column is row `i`.
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Pattern {
-    row_major: Vec<char>,
-    column_major: Vec<char>,
-    width: usize,
-    height: usize,
+    rows: Vec<u64>,
+    columns: Vec<u64>,
+}
+
+impl Pattern {
+    pub fn parse(src: &str) -> Self {
+        let width = src.lines().next().unwrap().len();
+        let height = src.lines().count();
+        assert!(
+            width <= 64 && height <= 64,
+            "patterns must fit in 64x64, got {}x{}",
+            height,
+            width
+        );
+        let mut rows = vec![0; height];
+        let mut columns = vec![0; width];
+        for (i, line) in src.lines().enumerate() {
+            assert_eq!(line.len(), width, "ragged pattern");
+            for (j, c) in line.bytes().enumerate() {
+                match c {
+                    b'#' => {
+                        rows[i] |= 1 << j;
+                        columns[j] |= 1 << i;
+                    }
+                    b'.' => {}
+                    _ => panic!("unexpected character {:?}", c as char),
+                }
+            }
+        }
+        Self { rows, columns }
+    }
+}
+
+/// Line a pattern is mirrored across.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Axis {
+    /// Vertical line with this many columns to its left.
+    Vertical(usize),
+    /// Horizontal line with this many rows above it.
+    Horizontal(usize),
+}
+
+impl Axis {
+    pub fn summary(&self) -> usize {
+        match *self {
+            Axis::Vertical(columns) => columns,
+            Axis::Horizontal(rows) => 100 * rows,
+        }
+    }
 }
 
 #[aoc_generator(day13)]
 pub fn generator(src: &str) -> Vec<Pattern> {
-    src.split("\n\n")
-        .map(|l| {
-            let iter = l.lines();
-            let width = iter.clone().next().unwrap().len();
-            let height = iter.clone().count();
-            let row_major = iter.flat_map(str::chars).collect::<Vec<_>>();
-            let column_major = matrix_transpose(&row_major, width, height);
-            Pattern {
-                row_major,
-                column_major,
-                width,
-                height,
-            }
-        })
-        .collect()
+    src.split("\n\n").map(Pattern::parse).collect()
 }
 
 #[aoc(day13, part1)]
 pub fn part1(input: &[Pattern]) -> usize {
-    input
-        .into_iter()
-        .map(
-            |Pattern {
-                 row_major,
-                 column_major,
-                 width,
-                 height,
-             }| {
-                let width = *width;
-                let height = *height;
-                let h_reflect = (1..width).find(|j| {
-                    let hl = usize::min(*j, width - j);
-                    (0..height)
-                        .all(|i| is_palindrome(&row_major[i * width + j - hl..i * width + j + hl]))
-                });
-                let v_reflect = (1..height)
-                    .find(|j| {
-                        let hl = usize::min(*j, height - j);
-                        (0..width).all(|i| {
-                            is_palindrome(&column_major[i * height + j - hl..i * height + j + hl])
-                        })
-                    })
-                    .map(|j| j * 100);
-                h_reflect.or(v_reflect).unwrap()
-            },
-        )
-        .map(|s| s)
-        .sum()
+    summarize(input, 0)
 }
 
 #[aoc(day13, part2)]
 pub fn part2(input: &[Pattern]) -> usize {
+    summarize(input, 1)
+}
+
+fn summarize(input: &[Pattern], smudges: usize) -> usize {
     input
-        .into_iter()
-        .map(
-            |Pattern {
-                 row_major,
-                 column_major,
-                 width,
-                 height,
-             }| {
-                let width = *width;
-                let height = *height;
-                let h_reflect = (1..width).find(|j| {
-                    let hl = usize::min(*j, width - j);
-                    (0..height)
-                        .map(|i| {
-                            maybe_palindrome(&row_major[i * width + j - hl..i * width + j + hl])
-                        })
-                        .sum::<usize>()
-                        == 1
-                });
-                let v_reflect = (1..height)
-                    .find(|j| {
-                        let hl = usize::min(*j, height - j);
-                        (0..width)
-                            .map(|i| {
-                                maybe_palindrome(
-                                    &column_major[i * height + j - hl..i * height + j + hl],
-                                )
-                            })
-                            .sum::<usize>()
-                            == 1
-                    })
-                    .map(|j| j * 100);
-                h_reflect.or(v_reflect).unwrap()
-            },
-        )
+        .iter()
+        .map(|pattern| find_reflection(pattern, smudges)[0].summary())
         .sum()
 }
 
-fn maybe_palindrome(target: &[char]) -> usize {
-    (0..target.len() / 2)
-        .filter(|&i| target[i] != target[target.len() - i - 1])
-        .count()
+/// Returns every axis across which the pattern would be mirrored if exactly
+/// `smudges` cells were flipped, vertical axes first, each from left to right
+/// or top to bottom.
+pub fn find_reflection(pattern: &Pattern, smudges: usize) -> Vec<Axis> {
+    reflections(&pattern.columns, smudges)
+        .map(Axis::Vertical)
+        .chain(reflections(&pattern.rows, smudges).map(Axis::Horizontal))
+        .collect()
 }
 
-fn is_palindrome(target: &[char]) -> bool {
-    (0..target.len()).all(|i| target[i] == target[target.len() - i - 1])
+/// Positions `k` such that mirroring `lines[..k]` onto `lines[k..]` differs in
+/// exactly `smudges` bits over the overlapping part.
+fn reflections(lines: &[u64], smudges: usize) -> impl Iterator<Item = usize> + '_ {
+    (1..lines.len()).filter(move |&k| {
+        let mut differences = 0;
+        for (a, b) in lines[..k].iter().rev().zip(&lines[k..]) {
+            differences += (a ^ b).count_ones() as usize;
+            if differences > smudges {
+                return false;
+            }
+        }
+        differences == smudges
+    })
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
 
+    const SAMPLE: &str = r"#.##..##.
+..#.##.#.
+##......#
+##......#
+..#.##.#.
+..##..##.
+#.#.##.#.
+
+#...##..#
+#....#..#
+..##..###
+#####.##.
+#####.##.
+..##..###
+#....#..#";
+
     #[test]
     fn part1_sample() {
         let input = generator(
@@ -159,4 +166,30 @@ mod test {
         );
         assert_eq!(part2(&input), 400);
     }
+
+    #[test]
+    fn find_all_reflections() {
+        let input = generator(SAMPLE);
+        assert_eq!(find_reflection(&input[0], 0), vec![Axis::Vertical(5)]);
+        assert_eq!(find_reflection(&input[1], 0), vec![Axis::Horizontal(4)]);
+        assert_eq!(find_reflection(&input[0], 1), vec![Axis::Horizontal(3)]);
+        assert_eq!(find_reflection(&input[1], 1), vec![Axis::Horizontal(1)]);
+
+        let blank = Pattern::parse("....\n....");
+        assert_eq!(
+            find_reflection(&blank, 0),
+            vec![
+                Axis::Vertical(1),
+                Axis::Vertical(2),
+                Axis::Vertical(3),
+                Axis::Horizontal(1)
+            ]
+        );
+        let corner = Pattern::parse("#...\n....");
+        assert_eq!(
+            find_reflection(&corner, 1),
+            vec![Axis::Vertical(1), Axis::Vertical(2), Axis::Horizontal(1)]
+        );
+        assert_eq!(find_reflection(&corner, 2), vec![]);
+    }
 }
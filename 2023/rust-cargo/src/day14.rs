@@ -1,11 +1,20 @@
-use std::{collections::HashMap, fmt::Display, hash::Hasher};
+use std::fmt::Display;
 
 use aoc_runner_derive::{aoc, aoc_generator};
 use grid::Grid;
-use itertools::Itertools;
 use num_derive::FromPrimitive;
 use num_traits::FromPrimitive;
 
+use crate::utils::{cycle::state_after, grid::S4Direction};
+
+const NUM_CYCLES: usize = 1000000000;
+const SPIN_CYCLE: [S4Direction; 4] = [
+    S4Direction::North,
+    S4Direction::West,
+    S4Direction::South,
+    S4Direction::East,
+];
+
 #[derive(Default, Debug, Clone, Copy, PartialEq, Eq, FromPrimitive, Hash)]
 #[repr(u8)]
 pub enum Tile {
@@ -25,7 +34,7 @@ impl Display for Tile {
 pub fn generator(src: &str) -> Grid<Tile> {
     let height = src.lines().count();
     let width = src.lines().next().unwrap().len();
-    let mut grid = Grid::<Tile>::new_with_order(width, height, grid::Order::ColumnMajor);
+    let mut grid = Grid::<Tile>::new_with_order(height, width, grid::Order::ColumnMajor);
     for (i, line) in src.lines().enumerate() {
         for (j, c) in line.bytes().enumerate() {
             grid[(i, j)] = FromPrimitive::from_u8(c).unwrap();
@@ -52,43 +61,204 @@ pub fn part1(input: &Grid<Tile>) -> usize {
 
 #[aoc(day14, part2)]
 pub fn part2(input: &Grid<Tile>) -> usize {
-    const NUM_CYCLES: usize = 1000000000;
-    let mut grid = input.clone();
-    let mut memo = HashMap::new();
-    let mut i = 0;
-    while i < NUM_CYCLES {
-        cycle(&mut grid);
-        let hash = hash_grid(&grid);
-        if let Some(&previous) = memo.get(&hash) {
-            let cycle_length = i - previous;
-            let remainder = NUM_CYCLES - i;
-            i = NUM_CYCLES - remainder % cycle_length;
-        }
-        memo.insert(hash, i);
-        i += 1;
+    load(&spin(input, &SPIN_CYCLE, NUM_CYCLES), S4Direction::North)
+}
+
+/// Tilts the platform so every round rock rolls as far as it can towards
+/// `direction`.
+pub fn tilt(grid: &mut Grid<Tile>, direction: S4Direction) {
+    match direction {
+        S4Direction::North => roll_towards(grid, north),
+        S4Direction::West => roll_towards(grid, west),
+        S4Direction::South => roll_towards(grid, south),
+        S4Direction::East => roll_towards(grid, east),
     }
+}
+
+/// Returns the platform after applying every tilt of `tilts` in order,
+/// `repeats` times over. Once the platform starts repeating, whole cycles are
+/// skipped.
+pub fn spin(grid: &Grid<Tile>, tilts: &[S4Direction], repeats: usize) -> Grid<Tile> {
+    state_after(
+        grid.clone(),
+        |grid| {
+            let mut grid = grid.clone();
+            for &direction in tilts {
+                tilt(&mut grid, direction);
+            }
+            grid
+        },
+        repeats,
+    )
+}
+
+/// Total load on the support beams along `edge`: each round rock weighs as
+/// many rows or columns as separate it from the opposite edge, counting its
+/// own.
+pub fn load(grid: &Grid<Tile>, edge: S4Direction) -> usize {
+    let (rows, cols) = grid.size();
     grid.indexed_iter()
-        .filter(|(_, &c)| c == Tile::RoundRock)
-        .map(|((i, _), _)| grid.rows() - i)
+        .filter(|(_, &t)| t == Tile::RoundRock)
+        .map(|((i, j), _)| match edge {
+            S4Direction::North => rows - i,
+            S4Direction::West => cols - j,
+            S4Direction::South => i + 1,
+            S4Direction::East => j + 1,
+        })
         .sum()
 }
 
-fn cycle(grid: &mut Grid<Tile>) {
-    roll_towards(grid, north);
-    roll_towards(grid, west);
-    roll_towards(grid, south);
-    roll_towards(grid, east);
+#[aoc(day14, part2, bitboard)]
+pub fn part2_bitboard(input: &Grid<Tile>) -> usize {
+    state_after(
+        Bitboard::new(input),
+        |board| {
+            let mut board = board.clone();
+            board.cycle();
+            board
+        },
+        NUM_CYCLES,
+    )
+    .north_load()
+}
+
+/// Platform stored as bitmasks, one per row, where bit `j` stands for column
+/// `j`. Cube rocks never move, so besides their masks only the runs of cells
+/// between them are kept, along both rows and columns, and a tilt packs the
+/// round rocks of each run against one of its ends.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bitboard {
+    rows: usize,
+    cols: usize,
+    round: Vec<u128>,
+    cube: Vec<u128>,
+    row_runs: Vec<Vec<u128>>,
+    col_runs: Vec<Vec<u128>>,
 }
 
-pub fn hash_grid(grid: &Grid<Tile>) -> u64 {
-    use std::hash::Hash;
-    let mut state = std::collections::hash_map::DefaultHasher::new();
-    grid.rows().hash(&mut state);
-    grid.cols().hash(&mut state);
-    for coords in (0..grid.rows()).cartesian_product(0..grid.cols()) {
-        grid[coords].hash(&mut state);
+impl Bitboard {
+    pub fn new(grid: &Grid<Tile>) -> Self {
+        let (rows, cols) = grid.size();
+        assert!(
+            rows <= 128 && cols <= 128,
+            "bitboards hold up to 128x128 tiles, got {}x{}",
+            rows,
+            cols
+        );
+        let mut round = vec![0; rows];
+        let mut cube = vec![0; rows];
+        for ((i, j), &t) in grid.indexed_iter() {
+            match t {
+                Tile::Empty => {}
+                Tile::RoundRock => round[i] |= 1 << j,
+                Tile::CubeRock => cube[i] |= 1 << j,
+            }
+        }
+        let row_runs = cube.iter().map(|&c| runs(c, cols)).collect();
+        let col_runs = transpose(&cube, cols)
+            .into_iter()
+            .map(|c| runs(c, rows))
+            .collect();
+        Self {
+            rows,
+            cols,
+            round,
+            cube,
+            row_runs,
+            col_runs,
+        }
+    }
+
+    pub fn tilt(&mut self, direction: S4Direction) {
+        use S4Direction::*;
+        match direction {
+            West | East => pack(&mut self.round, &self.row_runs, direction == West),
+            North | South => {
+                let mut columns = transpose(&self.round, self.cols);
+                pack(&mut columns, &self.col_runs, direction == North);
+                self.round = transpose(&columns, self.rows);
+            }
+        }
+    }
+
+    pub fn cycle(&mut self) {
+        for direction in SPIN_CYCLE {
+            self.tilt(direction);
+        }
+    }
+
+    pub fn north_load(&self) -> usize {
+        self.round
+            .iter()
+            .enumerate()
+            .map(|(i, r)| r.count_ones() as usize * (self.rows - i))
+            .sum()
+    }
+
+    pub fn to_grid(&self) -> Grid<Tile> {
+        let mut grid = Grid::new_with_order(self.rows, self.cols, grid::Order::ColumnMajor);
+        for (i, (&round, &cube)) in self.round.iter().zip(&self.cube).enumerate() {
+            for j in 0..self.cols {
+                if round >> j & 1 == 1 {
+                    grid[(i, j)] = Tile::RoundRock;
+                } else if cube >> j & 1 == 1 {
+                    grid[(i, j)] = Tile::CubeRock;
+                }
+            }
+        }
+        grid
+    }
+}
+
+/// Masks of the maximal runs of unset bits among the first `len` bits of `cube`.
+fn runs(cube: u128, len: usize) -> Vec<u128> {
+    let mut runs = Vec::new();
+    let mut start = 0;
+    for end in (0..len).filter(|&j| cube >> j & 1 == 1).chain([len]) {
+        if end > start {
+            runs.push(ones(end - start) << start);
+        }
+        start = end + 1;
     }
-    state.finish()
+    runs
+}
+
+/// Moves the set bits of each line to the low (or high) end of the run they
+/// are in.
+fn pack(lines: &mut [u128], runs: &[Vec<u128>], towards_low: bool) {
+    for (line, runs) in lines.iter_mut().zip(runs) {
+        let mut packed = 0;
+        for &run in runs {
+            let count = (*line & run).count_ones() as usize;
+            if count == 0 {
+                continue;
+            }
+            packed |= if towards_low {
+                ones(count) << run.trailing_zeros()
+            } else {
+                ones(count) << (128 - run.leading_zeros() as usize - count)
+            };
+        }
+        *line = packed;
+    }
+}
+
+/// Turns `masks[i]` bit `j` into bit `i` of line `j`, for `len` output lines.
+fn transpose(masks: &[u128], len: usize) -> Vec<u128> {
+    let mut out = vec![0; len];
+    for (i, &mask) in masks.iter().enumerate() {
+        let mut rest = mask;
+        while rest != 0 {
+            out[rest.trailing_zeros() as usize] |= 1 << i;
+            rest &= rest - 1;
+        }
+    }
+    out
+}
+
+#[inline]
+fn ones(n: usize) -> u128 {
+    1u128.checked_shl(n as u32).map_or(u128::MAX, |b| b - 1)
 }
 
 fn roll_towards<
@@ -183,6 +353,7 @@ fn east(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::utils::test_rng::XorShift;
 
     const SAMPLE: &'static str = r"O....#....
 O.OO#....#
@@ -208,4 +379,108 @@ O.#..O.#.#
     fn part2_sample() {
         assert_eq!(part2(&sample_input()), 64);
     }
+
+    #[test]
+    fn part2_bitboard_sample() {
+        assert_eq!(part2_bitboard(&sample_input()), 64);
+    }
+
+    /// Deterministic pseudo-random platforms of assorted shapes.
+    fn random_grids() -> impl Iterator<Item = Grid<Tile>> {
+        let mut rng = XorShift::default();
+        (0..50).map(move |k| {
+            let (rows, cols) = ((k % 7) * 19 + 1, (k % 11) * 12 + 8);
+            let mut grid = Grid::new_with_order(rows, cols, grid::Order::ColumnMajor);
+            for tile in grid.iter_mut() {
+                *tile = match rng.below(6) {
+                    0 | 1 => Tile::RoundRock,
+                    2 => Tile::CubeRock,
+                    _ => Tile::Empty,
+                };
+            }
+            grid
+        })
+    }
+
+    #[test]
+    fn bitboard_matches_grid() {
+        use S4Direction::*;
+        for mut grid in random_grids() {
+            let mut board = Bitboard::new(&grid);
+            assert_eq!(board.to_grid(), grid);
+            for direction in [North, West, South, East, East, North, South, West] {
+                board.tilt(direction);
+                tilt(&mut grid, direction);
+                assert_eq!(board.to_grid(), grid);
+            }
+            for _ in 0..3 {
+                board.cycle();
+                for direction in SPIN_CYCLE {
+                    tilt(&mut grid, direction);
+                }
+                assert_eq!(board.to_grid(), grid);
+            }
+        }
+        for grid in random_grids().step_by(10) {
+            assert_eq!(part2_bitboard(&grid), part2(&grid));
+        }
+    }
+
+    #[test]
+    fn spin_sample() {
+        let after = [
+            r".....#....
+....#...O#
+...OO##...
+.OO#......
+.....OOO#.
+.O#...O#.#
+....O#....
+......OOOO
+#...O###..
+#..OO#....",
+            r".....#....
+....#...O#
+.....##...
+..O#......
+.....OOO#.
+.O#...O#.#
+....O#...O
+.......OOO
+#..OO###..
+#.OOO#...O",
+            r".....#....
+....#...O#
+.....##...
+..O#......
+.....OOO#.
+.O#...O#.#
+....O#...O
+.......OOO
+#...O###.O
+#.OOO#...O",
+        ];
+        for (k, expected) in after.into_iter().enumerate() {
+            assert_eq!(
+                spin(&sample_input(), &SPIN_CYCLE, k + 1),
+                generator(expected)
+            );
+        }
+        assert_eq!(spin(&sample_input(), &SPIN_CYCLE, 0), sample_input());
+
+        use S4Direction::*;
+        let tilted = spin(&sample_input(), &[North], 1);
+        assert_eq!(load(&tilted, North), part1(&sample_input()));
+        assert_eq!(spin(&sample_input(), &[South, North], NUM_CYCLES), tilted);
+    }
+
+    #[test]
+    fn load_edges() {
+        use S4Direction::*;
+        let grid = generator("O..\n...");
+        assert_eq!(load(&grid, North), 2);
+        assert_eq!(load(&grid, South), 1);
+        assert_eq!(load(&grid, West), 3);
+        assert_eq!(load(&grid, East), 1);
+    }
 }
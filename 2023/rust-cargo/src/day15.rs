@@ -1,68 +1,377 @@
+use std::{
+    borrow::{Borrow, Cow},
+    fmt::Display,
+    hash::{BuildHasher, Hasher},
+};
+
 use aoc_runner_derive::aoc;
 
 #[aoc(day15, part1)]
 pub fn part1(input: &str) -> usize {
-    input.split(',').map(hash).sum()
+    steps(input).map(|step| hash(&step.text)).sum()
 }
 
 #[aoc(day15, part2)]
 pub fn part2(input: &str) -> usize {
-    let commands = input.split(',').map(|src| {
-        let mut iter = src.split(&['=', '-']);
-        let label = iter.next().unwrap();
-        let label_len = label.len();
-        match &src[label_len..label_len + 1] {
-            "=" => Command::Upsert {
-                lens: (label, iter.next().unwrap().parse().unwrap()),
-            },
-            "-" => Command::Remove { label },
-            _ => unreachable!(),
-        }
-    });
-    let mut hashmap = vec![Vec::new(); 256];
-    for command in commands {
-        match command {
-            Command::Upsert { lens } => {
-                let bucket = &mut hashmap[hash(lens.0)];
-                if let Some(position) = bucket.iter().position(|&(l, _)| l == lens.0) {
-                    bucket[position].1 = lens.1;
-                } else {
-                    bucket.push(lens);
-                }
+    let mut lenses = LensMap::new();
+    for command in commands(input) {
+        match command.unwrap_or_else(|e| panic!("{}", e)).1 {
+            Command::Upsert {
+                lens: (label, focal_length),
+            } => {
+                lenses.insert(label, focal_length);
+            }
+            Command::Remove { label } => {
+                lenses.remove(&label);
             }
-            Command::Remove { label } => hashmap[hash(label)].retain(|&(l, _)| l != label),
         }
     }
-    hashmap
-        .into_iter()
-        .enumerate()
-        .flat_map(|(b, bucket)| {
-            bucket
-                .into_iter()
-                .enumerate()
-                .map(move |(s, (_, f))| (b + 1) * (s + 1) * f)
+    lenses.focusing_power()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command<'a> {
+    Remove { label: Cow<'a, str> },
+    Upsert { lens: (Cow<'a, str>, usize) },
+}
+
+/// One comma-separated step of the initialization sequence, with newlines
+/// removed. `offset` is the byte offset of its first character in the input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Step<'a> {
+    pub offset: usize,
+    pub text: Cow<'a, str>,
+}
+
+impl<'a> Step<'a> {
+    pub fn command(&self) -> Result<Command<'a>, ParseError> {
+        let error = |kind| ParseError {
+            offset: self.offset,
+            step: self.text.to_string(),
+            kind,
+        };
+        let operation = self
+            .text
+            .find(['=', '-'])
+            .ok_or_else(|| error(ParseErrorKind::MissingOperation))?;
+        if operation == 0 {
+            return Err(error(ParseErrorKind::EmptyLabel));
+        }
+        let label = match &self.text {
+            Cow::Borrowed(text) => Cow::Borrowed(&text[..operation]),
+            Cow::Owned(text) => Cow::Owned(text[..operation].to_string()),
+        };
+        let argument = &self.text[operation + 1..];
+        if self.text.as_bytes()[operation] == b'-' {
+            return if argument.is_empty() {
+                Ok(Command::Remove { label })
+            } else {
+                Err(error(ParseErrorKind::UnexpectedFocalLength))
+            };
+        }
+        if argument.is_empty() {
+            return Err(error(ParseErrorKind::MissingFocalLength));
+        }
+        if !argument.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(error(ParseErrorKind::InvalidFocalLength));
+        }
+        let focal_length = argument
+            .parse()
+            .map_err(|_| error(ParseErrorKind::InvalidFocalLength))?;
+        Ok(Command::Upsert {
+            lens: (label, focal_length),
         })
-        .sum()
+    }
+}
+
+/// Splits the initialization sequence into steps without collecting it,
+/// ignoring newlines wherever they appear. An empty last step, as left by a
+/// trailing comma or an empty input, is not reported.
+pub fn steps(input: &str) -> Steps<'_> {
+    Steps {
+        src: input,
+        position: Some(0),
+    }
+}
+
+/// Parses every step into a command, along with the offset of the step.
+pub fn commands(
+    input: &str,
+) -> impl Iterator<Item = Result<(usize, Command<'_>), ParseError>> + '_ {
+    steps(input).map(|step| Ok((step.offset, step.command()?)))
+}
+
+pub struct Steps<'a> {
+    src: &'a str,
+    position: Option<usize>,
+}
+
+impl<'a> Iterator for Steps<'a> {
+    type Item = Step<'a>;
+
+    fn next(&mut self) -> Option<Step<'a>> {
+        let start = self.position?;
+        let rest = &self.src[start..];
+        let piece = match rest.find(',') {
+            Some(end) => {
+                self.position = Some(start + end + 1);
+                &rest[..end]
+            }
+            None => {
+                self.position = None;
+                rest
+            }
+        };
+        let trimmed = piece.trim_start_matches(is_newline);
+        let offset = start + piece.len() - trimmed.len();
+        let text = if trimmed.contains(is_newline) {
+            Cow::Owned(trimmed.replace(is_newline, ""))
+        } else {
+            Cow::Borrowed(trimmed)
+        };
+        if text.is_empty() && self.position.is_none() {
+            return None;
+        }
+        Some(Step { offset, text })
+    }
+}
+
+#[inline]
+fn is_newline(c: char) -> bool {
+    c == '\n' || c == '\r'
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    /// Byte offset of the offending step in the input.
+    pub offset: usize,
+    pub step: String,
+    pub kind: ParseErrorKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseErrorKind {
+    MissingOperation,
+    EmptyLabel,
+    MissingFocalLength,
+    InvalidFocalLength,
+    UnexpectedFocalLength,
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let reason = match self.kind {
+            ParseErrorKind::MissingOperation => "expected '=' or '-'",
+            ParseErrorKind::EmptyLabel => "empty label",
+            ParseErrorKind::MissingFocalLength => "missing focal length after '='",
+            ParseErrorKind::InvalidFocalLength => "focal length is not a number",
+            ParseErrorKind::UnexpectedFocalLength => "unexpected characters after '-'",
+        };
+        write!(
+            f,
+            "invalid step {:?} at byte {}: {}",
+            self.step, self.offset, reason
+        )
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+fn hash<K: HolidayHash + ?Sized>(key: &K) -> usize {
+    let mut hasher = HolidayHasher::default();
+    key.holiday_hash(&mut hasher);
+    hasher.finish() as usize
+}
+
+/// The Holiday ASCII String Helper algorithm: each byte is added to the
+/// current value, which is then multiplied by 17 modulo 256.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct HolidayHasher {
+    current_value: u8,
+}
+
+impl Hasher for HolidayHasher {
+    fn finish(&self) -> u64 {
+        self.current_value as u64
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.current_value = self.current_value.wrapping_add(b).wrapping_mul(17);
+        }
+    }
 }
 
-enum Command<'a> {
-    Remove { label: &'a str },
-    Upsert { lens: (&'a str, usize) },
+impl BuildHasher for HolidayHasher {
+    type Hasher = HolidayHasher;
+
+    fn build_hasher(&self) -> HolidayHasher {
+        HolidayHasher::default()
+    }
+}
+
+/// Keys of a [`LensMap`]. Unlike [`Hash`](std::hash::Hash), which may add its own framing (a
+/// `str` appends a terminator byte), this writes exactly the bytes the HASH
+/// algorithm is defined over.
+pub trait HolidayHash {
+    fn holiday_hash<H: Hasher>(&self, state: &mut H);
+}
+
+impl HolidayHash for [u8] {
+    fn holiday_hash<H: Hasher>(&self, state: &mut H) {
+        state.write(self);
+    }
+}
+
+impl HolidayHash for str {
+    fn holiday_hash<H: Hasher>(&self, state: &mut H) {
+        self.as_bytes().holiday_hash(state);
+    }
+}
+
+impl HolidayHash for String {
+    fn holiday_hash<H: Hasher>(&self, state: &mut H) {
+        self.as_str().holiday_hash(state);
+    }
+}
+
+impl HolidayHash for Cow<'_, str> {
+    fn holiday_hash<H: Hasher>(&self, state: &mut H) {
+        self.as_ref().holiday_hash(state);
+    }
 }
 
-fn hash(src: &str) -> usize {
-    let mut current_value = 0;
-    for c in src.chars() {
-        current_value += c as usize;
-        current_value *= 17;
-        current_value %= 256;
+impl<T: HolidayHash + ?Sized> HolidayHash for &T {
+    fn holiday_hash<H: Hasher>(&self, state: &mut H) {
+        (**self).holiday_hash(state);
+    }
+}
+
+/// Number of boxes in a [`LensMap`].
+pub const BOX_COUNT: usize = 256;
+
+/// Map that keeps its entries in 256 boxes chosen by hash, each box in
+/// insertion order. Replacing a value keeps its slot, and removing one shifts
+/// the rest of its box forward.
+#[derive(Debug, Clone)]
+pub struct LensMap<K, V, S = HolidayHasher> {
+    boxes: Vec<Vec<(K, V)>>,
+    hasher: S,
+}
+
+impl<K: HolidayHash + Eq, V> LensMap<K, V> {
+    pub fn new() -> Self {
+        Self::with_hasher(HolidayHasher::default())
+    }
+}
+
+impl<K: HolidayHash + Eq, V> Default for LensMap<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: HolidayHash + Eq, V, S: BuildHasher> LensMap<K, V, S> {
+    pub fn with_hasher(hasher: S) -> Self {
+        Self {
+            boxes: (0..BOX_COUNT).map(|_| Vec::new()).collect(),
+            hasher,
+        }
+    }
+
+    /// Index of the box `key` belongs in.
+    pub fn box_index<Q: HolidayHash + ?Sized>(&self, key: &Q) -> usize {
+        let mut hasher = self.hasher.build_hasher();
+        key.holiday_hash(&mut hasher);
+        (hasher.finish() % BOX_COUNT as u64) as usize
+    }
+
+    /// Inserts `value`, returning the value it replaced if `key` was present.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let b = self.box_index(&key);
+        let lens_box = &mut self.boxes[b];
+        match lens_box.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, v)) => Some(std::mem::replace(v, value)),
+            None => {
+                lens_box.push((key, value));
+                None
+            }
+        }
+    }
+
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: HolidayHash + Eq + ?Sized,
+    {
+        let b = self.box_index(key);
+        let lens_box = &mut self.boxes[b];
+        let position = lens_box.iter().position(|(k, _)| k.borrow() == key)?;
+        Some(lens_box.remove(position).1)
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: HolidayHash + Eq + ?Sized,
+    {
+        self.boxes[self.box_index(key)]
+            .iter()
+            .find(|(k, _)| k.borrow() == key)
+            .map(|(_, v)| v)
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: HolidayHash + Eq + ?Sized,
+    {
+        self.get(key).is_some()
+    }
+}
+
+impl<K, V, S> LensMap<K, V, S> {
+    pub fn len(&self) -> usize {
+        self.boxes.iter().map(Vec::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.boxes.iter().all(Vec::is_empty)
+    }
+
+    /// Entries of box `b`, in slot order.
+    pub fn lens_box(&self, b: usize) -> impl Iterator<Item = (&K, &V)> {
+        self.boxes[b].iter().map(|(k, v)| (k, v))
+    }
+
+    /// Every entry, box by box and in slot order within each box.
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.boxes.iter().flatten().map(|(k, v)| (k, v))
+    }
+
+    /// Sum over every entry of its box number times its slot number times its
+    /// value, with boxes and slots counted from one.
+    pub fn focusing_power(&self) -> usize
+    where
+        V: Copy + Into<usize>,
+    {
+        self.boxes
+            .iter()
+            .enumerate()
+            .flat_map(|(b, lens_box)| {
+                lens_box
+                    .iter()
+                    .enumerate()
+                    .map(move |(s, &(_, f))| (b + 1) * (s + 1) * f.into())
+            })
+            .sum()
     }
-    current_value
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::utils::test_rng::XorShift;
 
     #[test]
     pub fn hash_sample() {
@@ -80,4 +389,172 @@ mod tests {
         let input = "rn=1,cm-,qp=3,cm=2,qp-,pc=4,ot=9,ab=5,pc-,pc=6,ot=7";
         assert_eq!(part2(input), 145);
     }
+
+    #[test]
+    pub fn holiday_hasher() {
+        assert_eq!(hash(&String::from("rn")), 0);
+        assert_eq!(hash(&Cow::from("qp")), 1);
+        assert_eq!(hash(&b"HASH"[..]), 52);
+        assert_eq!(HolidayHasher::default().hash_one(0xffu8), 239);
+
+        let mut map = std::collections::HashMap::with_hasher(HolidayHasher::default());
+        map.insert("rn", 1);
+        map.insert("cm", 2);
+        assert_eq!(map.get("cm"), Some(&2));
+    }
+
+    #[test]
+    pub fn lens_map_operations() {
+        let mut lenses = LensMap::new();
+        assert!(lenses.is_empty());
+        assert_eq!(lenses.insert("rn", 1usize), None);
+        assert_eq!(lenses.insert("cm", 2), None);
+        assert_eq!(lenses.insert("qp", 3), None);
+        assert_eq!(lenses.box_index("rn"), 0);
+        assert_eq!(lenses.box_index("cm"), 0);
+        assert_eq!(lenses.insert("rn", 4), Some(1));
+        assert_eq!(
+            lenses.lens_box(0).collect::<Vec<_>>(),
+            vec![(&"rn", &4), (&"cm", &2)]
+        );
+        assert_eq!(lenses.remove("rn"), Some(4));
+        assert_eq!(lenses.remove("rn"), None);
+        assert_eq!(lenses.get("cm"), Some(&2));
+        assert!(!lenses.contains_key("rn"));
+        assert_eq!(lenses.len(), 2);
+        assert_eq!(
+            lenses.iter().collect::<Vec<_>>(),
+            vec![(&"cm", &2), (&"qp", &3)]
+        );
+        assert_eq!(lenses.focusing_power(), 2 + 2 * 3);
+
+        let mut owned = LensMap::new();
+        owned.insert(String::from("pc"), 4u8);
+        assert_eq!(owned.get("pc"), Some(&4));
+        assert_eq!(owned.focusing_power(), 4 * 4);
+    }
+
+    /// Random operations checked against a model with the semantics of an
+    /// insertion-ordered map: updates keep their position and removals shift
+    /// later entries forward.
+    #[test]
+    pub fn lens_map_matches_ordered_model() {
+        let mut rng = XorShift::default();
+        let labels = (0..40)
+            .map(|k| {
+                let len = 1 + k % 3;
+                (0..len)
+                    .map(|l| (b'a' + ((k * 7 + l * 3) % 26) as u8) as char)
+                    .collect::<String>()
+            })
+            .collect::<Vec<_>>();
+        for _ in 0..20 {
+            let mut lenses = LensMap::new();
+            let mut model: Vec<(String, usize)> = Vec::new();
+            for _ in 0..500 {
+                let label = &labels[rng.below(labels.len())];
+                if rng.below(3) == 0 {
+                    let expected = model
+                        .iter()
+                        .position(|(l, _)| l == label)
+                        .map(|p| model.remove(p).1);
+                    assert_eq!(lenses.remove(label.as_str()), expected);
+                } else {
+                    let focal_length = 1 + rng.below(9);
+                    let expected = match model.iter_mut().find(|(l, _)| l == label) {
+                        Some((_, f)) => Some(std::mem::replace(f, focal_length)),
+                        None => {
+                            model.push((label.clone(), focal_length));
+                            None
+                        }
+                    };
+                    assert_eq!(lenses.insert(label.clone(), focal_length), expected);
+                }
+            }
+            assert_eq!(lenses.len(), model.len());
+            for label in &labels {
+                let expected = model.iter().find(|(l, _)| l == label).map(|(_, f)| f);
+                assert_eq!(lenses.get(label.as_str()), expected);
+            }
+            let mut power = 0;
+            for b in 0..BOX_COUNT {
+                let expected = model
+                    .iter()
+                    .filter(|(l, _)| hash(l) == b)
+                    .map(|(l, f)| (l, f))
+                    .collect::<Vec<_>>();
+                power += (b + 1)
+                    * expected
+                        .iter()
+                        .enumerate()
+                        .map(|(s, (_, &f))| (s + 1) * f)
+                        .sum::<usize>();
+                assert_eq!(lenses.lens_box(b).collect::<Vec<_>>(), expected);
+            }
+            assert_eq!(lenses.focusing_power(), power);
+        }
+    }
+
+    #[test]
+    pub fn newlines_are_ignored() {
+        let input = "rn=1,cm-,qp=3,cm=2,qp-,pc=4,\not=9,ab=5,pc-,p\nc=6,ot=7\n";
+        assert_eq!(part1(input), 1320);
+        assert_eq!(part2(input), 145);
+        assert_eq!(steps("").count(), 0);
+        assert_eq!(steps("\n").count(), 0);
+    }
+
+    #[test]
+    pub fn command_offsets() {
+        let parsed = commands("rn=1,cm-,\nq\np=3\n")
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(
+            parsed,
+            vec![
+                (
+                    0,
+                    Command::Upsert {
+                        lens: ("rn".into(), 1)
+                    }
+                ),
+                (5, Command::Remove { label: "cm".into() }),
+                (
+                    10,
+                    Command::Upsert {
+                        lens: ("qp".into(), 3)
+                    }
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    pub fn command_errors() {
+        let first_error = |input| commands(input).find_map(Result::err).unwrap();
+        let error = first_error("rn=1,cm=,qp=3");
+        assert_eq!(error.kind, ParseErrorKind::MissingFocalLength);
+        assert_eq!((error.offset, error.step.as_str()), (5, "cm="));
+        assert_eq!(
+            error.to_string(),
+            "invalid step \"cm=\" at byte 5: missing focal length after '='"
+        );
+
+        let error = first_error("rn=1,=4");
+        assert_eq!((error.kind, error.offset), (ParseErrorKind::EmptyLabel, 5));
+        let error = first_error("rn=1,qp=3,ab=x7");
+        assert_eq!(
+            (error.kind, error.offset),
+            (ParseErrorKind::InvalidFocalLength, 10)
+        );
+        let error = first_error("rn=+1");
+        assert_eq!(error.kind, ParseErrorKind::InvalidFocalLength);
+        let error = first_error("rn=1,\nrn");
+        assert_eq!(
+            (error.kind, error.offset),
+            (ParseErrorKind::MissingOperation, 6)
+        );
+        let error = first_error("rn-1");
+        assert_eq!(error.kind, ParseErrorKind::UnexpectedFocalLength);
+    }
 }
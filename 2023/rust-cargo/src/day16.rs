@@ -1,11 +1,16 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 
 use aoc_runner_derive::{aoc, aoc_generator};
+use arrayvec::ArrayVec;
 use grid::Grid;
 use num_derive::FromPrimitive;
 use num_traits::FromPrimitive;
+use petgraph::{
+    algo::tarjan_scc,
+    prelude::{DiGraph, NodeIndex},
+};
 
-use crate::utils::grid::{parse_grid_with_u8, GridCoords};
+use crate::utils::grid::{bit_grid::BitGrid, parse_grid_with_u8, GridCoords};
 
 #[derive(Debug, Default, PartialEq, Eq, FromPrimitive, Clone, Copy)]
 #[repr(u8)]
@@ -19,13 +24,62 @@ pub enum Tile {
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
-enum Direction {
+pub enum Direction {
     West,
     North,
     East,
     South,
 }
 
+const ALL_DIRECTIONS: [Direction; 4] = [
+    Direction::West,
+    Direction::North,
+    Direction::East,
+    Direction::South,
+];
+
+impl Direction {
+    pub fn arrow(self) -> char {
+        match self {
+            Direction::West => '<',
+            Direction::North => '^',
+            Direction::East => '>',
+            Direction::South => 'v',
+        }
+    }
+}
+
+/// Set of directions, one bit per [`Direction`].
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy, Hash)]
+pub struct DirectionSet(u8);
+
+impl DirectionSet {
+    /// Adds `direction`, returning whether it was absent.
+    pub fn insert(&mut self, direction: Direction) -> bool {
+        let absent = !self.contains(direction);
+        self.0 |= 1 << direction as u8;
+        absent
+    }
+
+    pub fn contains(self, direction: Direction) -> bool {
+        self.0 & 1 << direction as u8 != 0
+    }
+
+    pub fn len(self) -> usize {
+        self.0.count_ones() as usize
+    }
+
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn iter(self) -> impl Iterator<Item = Direction> {
+        ALL_DIRECTIONS
+            .into_iter()
+            .filter(move |&d| self.contains(d))
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
 struct Ray(GridCoords, Direction);
 
@@ -85,55 +139,228 @@ pub fn part1(layout: &Grid<Tile>) -> usize {
 
 #[aoc(day16, part2)]
 pub fn part2(layout: &Grid<Tile>) -> usize {
+    let graph = BeamGraph::new(layout);
+    let energized = graph.energized_tiles();
+    edge_rays(layout)
+        .map(|r| energized[graph.component(r)].count_ones())
+        .max()
+        .unwrap()
+}
+
+#[aoc(day16, part2, per_ray)]
+pub fn part2_per_ray(layout: &Grid<Tile>) -> usize {
+    edge_rays(layout)
+        .map(|r| beam_from(r, layout))
+        .max()
+        .unwrap()
+}
+
+/// Every ray entering the layout from its edges, clockwise from the top row.
+fn edge_rays(layout: &Grid<Tile>) -> impl Iterator<Item = Ray> {
     let rows = layout.rows();
     let cols = layout.cols();
     let top_row = (0..cols).map(|j| Ray((0, j), Direction::South));
-    let right_col = (0..rows).map(|i| Ray((i, cols - 1), Direction::West));
-    let bottom_row = (0..cols).map(|j| Ray((rows - 1, j), Direction::North));
+    let right_col = (0..rows).map(move |i| Ray((i, cols - 1), Direction::West));
+    let bottom_row = (0..cols).map(move |j| Ray((rows - 1, j), Direction::North));
     let left_col = (0..rows).map(|i| Ray((i, 0), Direction::East));
-    top_row
-        .chain(right_col)
-        .chain(bottom_row)
-        .chain(left_col)
-        .map(|r| beam_from(r, layout))
-        .max()
-        .unwrap()
+    top_row.chain(right_col).chain(bottom_row).chain(left_col)
 }
 
 fn beam_from(initial_ray: Ray, layout: &Grid<Tile>) -> usize {
-    use Direction::*;
-    use Tile::*;
-    let cols = layout.cols();
-    let rows = layout.rows();
-    let dimensions = (cols, rows);
     let mut rays = vec![initial_ray];
-    let mut energized_tiles = Grid::new(rows, cols);
+    let mut energized_tiles = BitGrid::new(layout.rows(), layout.cols());
     let mut previous_rays = HashSet::new();
     while let Some(ray) = rays.pop() {
         if previous_rays.contains(&ray) {
             continue;
         }
         previous_rays.insert(ray);
-        energized_tiles[ray.0] = true;
-        let tile = layout[ray.0];
-        match (tile, ray.1) {
-            (Empty, _) | (SplitterHorizontal, East | West) | (SplitterVertical, North | South) => {
-                rays.extend(ray.traverse(&dimensions))
+        energized_tiles.set(ray.0, true);
+        rays.extend(outgoing(ray, layout));
+    }
+    energized_tiles.count_ones()
+}
+
+/// Tiles crossed by a beam, and the directions it was heading in as it
+/// entered each of them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Coverage {
+    pub energized: Grid<bool>,
+    pub directions: Grid<DirectionSet>,
+}
+
+/// Follows the beam entering `start` heading towards `direction`.
+pub fn trace_beam(layout: &Grid<Tile>, start: GridCoords, direction: Direction) -> Coverage {
+    let mut rays = vec![Ray(start, direction)];
+    let mut energized = Grid::new(layout.rows(), layout.cols());
+    let mut directions = Grid::<DirectionSet>::new(layout.rows(), layout.cols());
+    while let Some(ray) = rays.pop() {
+        if !directions[ray.0].insert(ray.1) {
+            continue;
+        }
+        energized[ray.0] = true;
+        rays.extend(outgoing(ray, layout));
+    }
+    Coverage {
+        energized,
+        directions,
+    }
+}
+
+/// Draws the layout with the beams over its empty tiles, as in the puzzle
+/// text: an arrow where a single beam passed, or the number of beams.
+pub fn render_beams(layout: &Grid<Tile>, coverage: &Coverage) -> String {
+    let mut out = String::with_capacity(layout.rows() * (layout.cols() + 1));
+    for i in 0..layout.rows() {
+        for j in 0..layout.cols() {
+            let directions = coverage.directions[(i, j)];
+            out.push(match (layout[(i, j)], directions.len()) {
+                (Tile::Empty, 0) => '.',
+                (Tile::Empty, 1) => directions.iter().next().unwrap().arrow(),
+                (Tile::Empty, n) => char::from_digit(n as u32, 10).unwrap(),
+                (tile, _) => tile as u8 as char,
+            });
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// Number of beams entering from the edges of the layout that energize each
+/// tile.
+pub fn heatmap(layout: &Grid<Tile>) -> Grid<usize> {
+    let graph = BeamGraph::new(layout);
+    let energized = graph.energized_tiles();
+    let mut heat = Grid::new(layout.rows(), layout.cols());
+    for ray in edge_rays(layout) {
+        for coords in energized[graph.component(ray)].iter_ones() {
+            heat[coords] += 1;
+        }
+    }
+    heat
+}
+
+/// Rays leaving the tile `ray` is on, already moved onto their next tile.
+fn outgoing(ray: Ray, layout: &Grid<Tile>) -> ArrayVec<Ray, 2> {
+    use Direction::*;
+    use Tile::*;
+    let dimensions = (layout.cols(), layout.rows());
+    let mut out = ArrayVec::new();
+    match (layout[ray.0], ray.1) {
+        (Empty, _) | (SplitterHorizontal, East | West) | (SplitterVertical, North | South) => {
+            out.extend(ray.traverse(&dimensions))
+        }
+        (MirrorNegative, _) => out.extend(ray.mirror_negative().traverse(&dimensions)),
+        (MirrorPositive, _) => out.extend(ray.mirror_positive().traverse(&dimensions)),
+        (SplitterHorizontal | SplitterVertical, _) => {
+            out.extend(ray.mirror_negative().traverse(&dimensions));
+            out.extend(ray.mirror_positive().traverse(&dimensions));
+        }
+    }
+    out
+}
+
+/// Beams cut into segments at splitters. Each node is a ray entering either a
+/// splitter or the layout from an edge, and holds the tiles the beam crosses
+/// from there until it enters the next splitter or leaves the layout, with an
+/// edge to the node of each splitter it enters.
+///
+/// Between splitters a beam only meets mirrors and empty tiles, which never
+/// send two rays to the same place, so every segment is finite.
+struct BeamGraph {
+    graph: DiGraph<Vec<GridCoords>, ()>,
+    nodes: HashMap<Ray, NodeIndex>,
+    components: Vec<usize>,
+    component_count: usize,
+    rows: usize,
+    cols: usize,
+}
+
+impl BeamGraph {
+    pub fn new(layout: &Grid<Tile>) -> Self {
+        let mut graph: DiGraph<Vec<GridCoords>, ()> = DiGraph::new();
+        let mut nodes = HashMap::new();
+        let mut pending = edge_rays(layout).collect::<Vec<_>>();
+        for &ray in &pending {
+            nodes
+                .entry(ray)
+                .or_insert_with(|| graph.add_node(Vec::new()));
+        }
+        while let Some(start) = pending.pop() {
+            let node = nodes[&start];
+            let mut tiles = vec![start.0];
+            let mut rays = outgoing(start, layout);
+            while let Some(ray) = rays.pop() {
+                if matches!(
+                    layout[ray.0],
+                    Tile::SplitterHorizontal | Tile::SplitterVertical
+                ) {
+                    let successor = *nodes.entry(ray).or_insert_with(|| {
+                        pending.push(ray);
+                        graph.add_node(Vec::new())
+                    });
+                    graph.add_edge(node, successor, ());
+                } else {
+                    tiles.push(ray.0);
+                    rays.extend(outgoing(ray, layout));
+                }
             }
-            (MirrorNegative, _) => rays.extend(ray.mirror_negative().traverse(&dimensions)),
-            (MirrorPositive, _) => rays.extend(ray.mirror_positive().traverse(&dimensions)),
-            (SplitterHorizontal | SplitterVertical, _) => {
-                rays.extend(ray.mirror_negative().traverse(&dimensions));
-                rays.extend(ray.mirror_positive().traverse(&dimensions));
+            graph[node] = tiles;
+        }
+
+        let sccs = tarjan_scc(&graph);
+        let mut components = vec![0; graph.node_count()];
+        for (c, scc) in sccs.iter().enumerate() {
+            for node in scc {
+                components[node.index()] = c;
             }
         }
+        Self {
+            graph,
+            nodes,
+            components,
+            component_count: sccs.len(),
+            rows: layout.rows(),
+            cols: layout.cols(),
+        }
+    }
+
+    /// Strongly connected component holding the node of `ray`. Components are
+    /// numbered so that every edge goes to a component with a lower number.
+    pub fn component(&self, ray: Ray) -> usize {
+        self.components[self.nodes[&ray].index()]
+    }
+
+    /// Tiles energized by a beam starting anywhere in each component.
+    pub fn energized_tiles(&self) -> Vec<BitGrid> {
+        let mut nodes_by_component = vec![Vec::new(); self.component_count];
+        for node in self.graph.node_indices() {
+            nodes_by_component[self.components[node.index()]].push(node);
+        }
+        let mut energized: Vec<BitGrid> = Vec::with_capacity(self.component_count);
+        for (c, nodes) in nodes_by_component.into_iter().enumerate() {
+            let mut tiles = BitGrid::new(self.rows, self.cols);
+            for node in nodes {
+                for &coords in &self.graph[node] {
+                    tiles.set(coords, true);
+                }
+                for successor in self.graph.neighbors(node) {
+                    let d = self.components[successor.index()];
+                    if d != c {
+                        tiles |= &energized[d];
+                    }
+                }
+            }
+            energized.push(tiles);
+        }
+        energized
     }
-    energized_tiles.iter().copied().filter(|&e| e).count()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::utils::test_rng::XorShift;
 
     const SAMPLE_SRC: &'static str = r".|...\....
 |.-.\.....
@@ -159,4 +386,75 @@ mod tests {
     pub fn part2_sample() {
         assert_eq!(part2(&sample_input()), 51);
     }
+
+    #[test]
+    pub fn part2_per_ray_sample() {
+        assert_eq!(part2_per_ray(&sample_input()), 51);
+    }
+
+    #[test]
+    pub fn beam_graph_matches_beam_from() {
+        let mut rng = XorShift::default();
+        let random_layouts = (0..20).map(|k| {
+            let (rows, cols) = (5 + k % 4 * 6, 4 + k % 5 * 5);
+            let mut layout = Grid::new(rows, cols);
+            for tile in layout.iter_mut() {
+                *tile = match rng.below(12) {
+                    0 => Tile::MirrorNegative,
+                    1 => Tile::MirrorPositive,
+                    2 => Tile::SplitterVertical,
+                    3 => Tile::SplitterHorizontal,
+                    _ => Tile::Empty,
+                };
+            }
+            layout
+        });
+        for layout in [sample_input()].into_iter().chain(random_layouts) {
+            let graph = BeamGraph::new(&layout);
+            let energized = graph.energized_tiles();
+            for ray in edge_rays(&layout) {
+                assert_eq!(
+                    energized[graph.component(ray)].count_ones(),
+                    beam_from(ray, &layout)
+                );
+            }
+        }
+    }
+
+    #[test]
+    pub fn render_beams_sample() {
+        let layout = sample_input();
+        let coverage = trace_beam(&layout, (0, 0), Direction::East);
+        let expected = r">|<<<\....
+|v-.\^....
+.v...|->>>
+.v...v^.|.
+.v...v^...
+.v...v^..\
+.v../2\\..
+<->-/vv|..
+.|<<<2-|.\
+.v//.|.v..
+";
+        assert_eq!(render_beams(&layout, &coverage), expected);
+        assert_eq!(coverage.energized.iter().filter(|&&e| e).count(), 46);
+        assert_eq!(coverage.directions[(6, 5)].len(), 2);
+        assert_eq!(
+            coverage.directions[(0, 1)].iter().collect::<Vec<_>>(),
+            vec![Direction::West, Direction::East]
+        );
+    }
+
+    #[test]
+    pub fn heatmap_sample() {
+        let layout = sample_input();
+        let mut expected = Grid::new(layout.rows(), layout.cols());
+        for ray in edge_rays(&layout) {
+            let coverage = trace_beam(&layout, ray.0, ray.1);
+            for (coords, &e) in coverage.energized.indexed_iter() {
+                expected[coords] += e as usize;
+            }
+        }
+        assert_eq!(heatmap(&layout), expected);
+    }
 }
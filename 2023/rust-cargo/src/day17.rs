@@ -1,8 +1,11 @@
+use std::hash::{Hash, Hasher};
+
 use aoc_runner_derive::{aoc, aoc_generator};
 use grid::Grid;
 
 use crate::utils::grid::{
     a_star::{grid_a_star, Cursor},
+    dial::{grid_dial, DenseCursor},
     grid_distance, grid_translate, parse_grid_with_u8, GridCoords, GridDimensions, S4Direction,
     ALL_S4_DIRECTIONS,
 };
@@ -14,28 +17,263 @@ pub fn generator(src: &str) -> Grid<u8> {
 
 #[aoc(day17, part1)]
 pub fn part1(input: &Grid<u8>) -> usize {
-    let dimensions = (input.rows(), input.cols());
-    let start = (0, 0);
-    let start_cursors = ALL_S4_DIRECTIONS.clone().into_iter().flat_map(|d| {
-        grid_translate(start, d.vector(1), &dimensions)
-            .map(|coords| CrucibleCursor::<0, 3>::new(coords, d, 0))
-    });
-    let end = (dimensions.0 - 1, dimensions.1 - 1);
-    let end_fn = |cursor: &CrucibleCursor<0, 3>| cursor.coords == end;
-    grid_a_star(&input, start_cursors, end_fn)
+    CrucibleProblem::new(input, 1, 3).solve(input)
 }
 
 #[aoc(day17, part2)]
 pub fn part2(input: &Grid<u8>) -> usize {
-    let dimensions = (input.rows(), input.cols());
-    let start = (0, 0);
-    let start_cursors = ALL_S4_DIRECTIONS.clone().into_iter().flat_map(|d| {
-        grid_translate(start, d.vector(1), &dimensions)
-            .map(|coords| CrucibleCursor::<4, 10>::new(coords, d, 0))
-    });
-    let end = (dimensions.0 - 1, dimensions.1 - 1);
-    let end_fn = |cursor: &CrucibleCursor<4, 10>| cursor.coords == end;
-    grid_a_star(&input, start_cursors, end_fn)
+    CrucibleProblem::ultra(input).solve(input)
+}
+
+#[aoc(day17, part1, dial)]
+pub fn part1_dial(input: &Grid<u8>) -> usize {
+    CrucibleProblem::new(input, 1, 3).solve_dial(input)
+}
+
+#[aoc(day17, part2, dial)]
+pub fn part2_dial(input: &Grid<u8>) -> usize {
+    CrucibleProblem::ultra(input).solve_dial(input)
+}
+
+#[aoc(day17, part2, runtime)]
+pub fn part2_runtime(input: &Grid<u8>) -> usize {
+    CrucibleProblem::ultra(input).solve_runtime(input)
+}
+
+/// Least heat loss from `start` to `end` for a crucible that must move at least
+/// `min_run` and at most `max_run` blocks in a straight line before turning.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CrucibleProblem {
+    pub start: GridCoords,
+    pub end: GridCoords,
+    pub min_run: u8,
+    pub max_run: u8,
+    /// Extra heat lost on every turn.
+    pub turn_penalty: usize,
+    /// Whether the crucible must also have moved at least `min_run` blocks
+    /// in a straight line when it reaches `end`.
+    pub final_minimum: bool,
+}
+
+impl CrucibleProblem {
+    /// Goes from the top-left to the bottom-right corner of `grid`, without
+    /// turn penalties or a final minimum.
+    pub fn new(grid: &Grid<u8>, min_run: u8, max_run: u8) -> Self {
+        Self {
+            start: (0, 0),
+            end: (grid.rows() - 1, grid.cols() - 1),
+            min_run,
+            max_run,
+            turn_penalty: 0,
+            final_minimum: false,
+        }
+    }
+
+    /// The ultra crucible of part 2, which moves 4 to 10 blocks in a straight
+    /// line, including the last run into `end`.
+    pub fn ultra(grid: &Grid<u8>) -> Self {
+        Self {
+            final_minimum: true,
+            ..Self::new(grid, 4, 10)
+        }
+    }
+
+    /// Panics if `end` cannot be reached.
+    pub fn solve(&self, grid: &Grid<u8>) -> usize {
+        self.dispatch(grid, Search::AStar)
+    }
+
+    /// Same as [`CrucibleProblem::solve`], searching with [`grid_dial`].
+    pub fn solve_dial(&self, grid: &Grid<u8>) -> usize {
+        self.dispatch(grid, Search::Dial)
+    }
+
+    /// Same as [`CrucibleProblem::solve`], always reading the constraints at
+    /// runtime.
+    pub fn solve_runtime(&self, grid: &Grid<u8>) -> usize {
+        self.solve_runtime_with(grid, Search::AStar)
+    }
+
+    /// Uses the const-generic cursors when they fit the problem. Their
+    /// heuristic assumes the bottom-right corner is the end, which only
+    /// matters to A*.
+    fn dispatch(&self, grid: &Grid<u8>, search: Search) -> usize {
+        let bottom_right = (grid.rows() - 1, grid.cols() - 1);
+        if self.turn_penalty != 0 || (search == Search::AStar && self.end != bottom_right) {
+            return self.solve_runtime_with(grid, search);
+        }
+        match (self.min_run.max(1), self.max_run) {
+            (1, 3) => self.solve_with::<CrucibleCursor<0, 3>>(
+                grid,
+                |coords, d| CrucibleCursor::new(coords, d, 0),
+                search,
+            ),
+            (4, 10) => self.solve_with::<CrucibleCursor<4, 10>>(
+                grid,
+                |coords, d| CrucibleCursor::new(coords, d, 0),
+                search,
+            ),
+            _ => self.solve_runtime_with(grid, search),
+        }
+    }
+
+    fn solve_runtime_with(&self, grid: &Grid<u8>, search: Search) -> usize {
+        self.solve_with(
+            grid,
+            |coords, direction| RuntimeCrucibleCursor {
+                coords,
+                direction,
+                steps_walked: 0,
+                turned: false,
+                problem: self,
+            },
+            search,
+        )
+    }
+
+    fn solve_with<C: DenseCursor<u8> + CrucibleState>(
+        &self,
+        grid: &Grid<u8>,
+        start_cursor: impl Fn(GridCoords, S4Direction) -> C,
+        search: Search,
+    ) -> usize {
+        let dimensions = (grid.rows(), grid.cols());
+        let start_cursors = ALL_S4_DIRECTIONS.into_iter().flat_map(|d| {
+            grid_translate(self.start, d.vector(1), &dimensions)
+                .map(|coords| start_cursor(coords, d))
+        });
+        let end_fn = |cursor: &C| {
+            cursor.coords() == self.end
+                && (!self.final_minimum || cursor.steps_walked() + 1 >= self.min_run)
+        };
+        match search {
+            Search::AStar => grid_a_star(grid, start_cursors, end_fn),
+            Search::Dial => grid_dial(grid, start_cursors, end_fn),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Search {
+    AStar,
+    Dial,
+}
+
+trait CrucibleState {
+    fn coords(&self) -> GridCoords;
+    fn steps_walked(&self) -> u8;
+}
+
+impl<const M: u8, const N: u8> CrucibleState for CrucibleCursor<M, N> {
+    fn coords(&self) -> GridCoords {
+        self.coords
+    }
+
+    fn steps_walked(&self) -> u8 {
+        self.steps_walked
+    }
+}
+
+/// Cursor reading its constraints from a [`CrucibleProblem`]. Every cursor of a
+/// search shares the same problem, so it is left out of comparisons.
+#[derive(Debug, Clone, Copy)]
+struct RuntimeCrucibleCursor<'p> {
+    coords: GridCoords,
+    direction: S4Direction,
+    steps_walked: u8,
+    turned: bool,
+    problem: &'p CrucibleProblem,
+}
+
+impl PartialEq for RuntimeCrucibleCursor<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.state() == other.state()
+    }
+}
+
+impl Eq for RuntimeCrucibleCursor<'_> {}
+
+impl Hash for RuntimeCrucibleCursor<'_> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.state().hash(state);
+    }
+}
+
+impl CrucibleState for RuntimeCrucibleCursor<'_> {
+    fn coords(&self) -> GridCoords {
+        self.coords
+    }
+
+    fn steps_walked(&self) -> u8 {
+        self.steps_walked
+    }
+}
+
+impl RuntimeCrucibleCursor<'_> {
+    #[inline]
+    fn state(&self) -> (GridCoords, S4Direction, u8, bool) {
+        (self.coords, self.direction, self.steps_walked, self.turned)
+    }
+
+    fn walk(&self, direction: S4Direction, dimensions: &GridDimensions) -> Option<Self> {
+        let turned = direction != self.direction;
+        let steps_walked = if turned { 0 } else { self.steps_walked + 1 };
+        if (turned && self.steps_walked + 1 < self.problem.min_run)
+            || (!turned && steps_walked >= self.problem.max_run)
+        {
+            return None;
+        }
+        Some(Self {
+            coords: grid_translate(self.coords, direction.vector(1), dimensions)?,
+            direction,
+            steps_walked,
+            turned,
+            problem: self.problem,
+        })
+    }
+}
+
+impl DenseCursor<u8> for RuntimeCrucibleCursor<'_> {
+    fn index_bound(&self, &(rows, cols): &GridDimensions) -> usize {
+        rows * cols * 4 * self.problem.max_run as usize * 2
+    }
+
+    fn index(&self, &(_, cols): &GridDimensions) -> usize {
+        let (i, j) = self.coords;
+        (((i * cols + j) * 4 + self.direction as usize) * self.problem.max_run as usize
+            + self.steps_walked as usize)
+            * 2
+            + self.turned as usize
+    }
+}
+
+impl Cursor<u8> for RuntimeCrucibleCursor<'_> {
+    type Neighbors<'s> =  std::iter::Flatten<<[Option<Self>;3] as IntoIterator>::IntoIter>
+    where
+        Self: 's;
+
+    fn neighbors<'s: 'd, 'd>(&'s self, dimensions: &'d GridDimensions) -> Self::Neighbors<'d> {
+        [
+            self.walk(self.direction.turn_left(), dimensions),
+            self.walk(self.direction.turn_right(), dimensions),
+            self.walk(self.direction, dimensions),
+        ]
+        .into_iter()
+        .flatten()
+    }
+
+    fn h(&self, _grid: &Grid<u8>) -> usize {
+        grid_distance(self.coords, self.problem.end)
+    }
+
+    fn c(&self, grid: &Grid<u8>) -> usize {
+        let penalty = if self.turned {
+            self.problem.turn_penalty
+        } else {
+            0
+        };
+        grid[self.coords] as usize + penalty
+    }
 }
 
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Hash)]
@@ -91,6 +329,17 @@ impl<const M: u8, const N: u8> CrucibleCursor<M, N> {
     }
 }
 
+impl<const M: u8, const N: u8> DenseCursor<u8> for CrucibleCursor<M, N> {
+    fn index_bound(&self, &(rows, cols): &GridDimensions) -> usize {
+        rows * cols * 4 * N as usize
+    }
+
+    fn index(&self, &(_, cols): &GridDimensions) -> usize {
+        let (i, j) = self.coords;
+        ((i * cols + j) * 4 + self.direction as usize) * N as usize + self.steps_walked as usize
+    }
+}
+
 impl<const M: u8, const N: u8> Cursor<u8> for CrucibleCursor<M, N> {
     type Neighbors<'s> =  std::iter::Flatten<<[Option<Self>;3] as IntoIterator>::IntoIter>
     where
@@ -119,6 +368,7 @@ impl<const M: u8, const N: u8> Cursor<u8> for CrucibleCursor<M, N> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::utils::test_rng::XorShift;
 
     const SAMPLE: &'static str = r"2413432311323
 3215453535623
@@ -134,6 +384,12 @@ mod tests {
 2546548887735
 4322674655533";
 
+    const SECOND_SAMPLE: &'static str = r"111111111111
+999999999991
+999999999991
+999999999991
+999999999991";
+
     fn sample_input() -> Grid<u8> {
         generator(SAMPLE)
     }
@@ -147,4 +403,92 @@ mod tests {
     pub fn part2_sample() {
         assert_eq!(part2(&sample_input()), 94);
     }
+
+    #[test]
+    pub fn part2_second_sample() {
+        let input = generator(SECOND_SAMPLE);
+        assert_eq!(part2(&input), 71);
+        assert_eq!(part2_runtime(&input), 71);
+    }
+
+    #[test]
+    pub fn runtime_matches_const() {
+        let input = sample_input();
+        for (min_run, max_run) in [(1, 3), (4, 10)] {
+            let problem = CrucibleProblem::new(&input, min_run, max_run);
+            assert_eq!(problem.solve_runtime(&input), problem.solve(&input));
+        }
+        assert_eq!(part2_runtime(&input), 94);
+    }
+
+    #[test]
+    pub fn final_minimum() {
+        let input = generator(SECOND_SAMPLE);
+        let mut problem = CrucibleProblem::new(&input, 4, 10);
+        problem.final_minimum = true;
+        assert_eq!(problem.solve(&input), 71);
+        assert_eq!(problem.solve_runtime(&input), 71);
+    }
+
+    #[test]
+    pub fn start_end_and_turn_penalty() {
+        let input = generator("12\n34");
+        let mut problem = CrucibleProblem::new(&input, 1, 3);
+        problem.start = (1, 1);
+        problem.end = (0, 0);
+        assert_eq!(problem.solve(&input), 3);
+
+        let input = generator("111\n111\n111");
+        let mut problem = CrucibleProblem::new(&input, 1, 3);
+        assert_eq!(problem.solve(&input), 4);
+        problem.turn_penalty = 10;
+        assert_eq!(problem.solve(&input), 14);
+        problem.max_run = 2;
+        assert_eq!(problem.solve(&input), 14);
+    }
+
+    #[test]
+    pub fn dial_matches_a_star() {
+        let input = sample_input();
+        assert_eq!(part1_dial(&input), 102);
+        assert_eq!(part2_dial(&input), 94);
+        assert_eq!(part2_dial(&generator(SECOND_SAMPLE)), 71);
+        let mut problem = CrucibleProblem::new(&input, 2, 5);
+        problem.final_minimum = true;
+        assert_eq!(problem.solve_dial(&input), problem.solve(&input));
+        problem.turn_penalty = 3;
+        problem.start = (4, 7);
+        assert_eq!(problem.solve_dial(&input), problem.solve(&input));
+    }
+
+    fn random_grid(rows: usize, cols: usize) -> Grid<u8> {
+        let mut rng = XorShift::default();
+        let mut grid = Grid::new(rows, cols);
+        for cell in grid.iter_mut() {
+            *cell = 1 + rng.below(9) as u8;
+        }
+        grid
+    }
+
+    /// Run with `cargo test --release bench_dial -- --ignored --nocapture`.
+    #[test]
+    #[ignore]
+    pub fn bench_dial_against_a_star() {
+        use std::time::Instant;
+        let input = random_grid(141, 141);
+        for (min_run, max_run) in [(1, 3), (4, 10)] {
+            let problem = CrucibleProblem::new(&input, min_run, max_run);
+            let start = Instant::now();
+            let a_star = problem.solve(&input);
+            let a_star_time = start.elapsed();
+            let start = Instant::now();
+            let dial = problem.solve_dial(&input);
+            let dial_time = start.elapsed();
+            assert_eq!(a_star, dial);
+            println!(
+                "runs {}..={}: grid_a_star {:?}, grid_dial {:?}",
+                min_run, max_run, a_star_time, dial_time
+            );
+        }
+    }
 }
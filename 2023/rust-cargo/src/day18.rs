@@ -1,10 +1,19 @@
-use std::str::FromStr;
+use std::{
+    fmt::{Display, Write},
+    str::FromStr,
+};
 
 use aoc_runner_derive::{aoc, aoc_generator};
+use grid::Grid;
 use num_derive::FromPrimitive;
 use num_traits::FromPrimitive;
 
-#[derive(Debug, Clone, Copy, FromPrimitive)]
+use crate::utils::{
+    geometry::{LatticePoint, Polygon},
+    grid::{GridVector, S4Direction},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromPrimitive)]
 enum Direction {
     Right = 0,
     Down = 1,
@@ -27,62 +36,308 @@ impl FromStr for Direction {
     }
 }
 
-#[derive(Debug)]
+impl From<Direction> for S4Direction {
+    fn from(direction: Direction) -> Self {
+        match direction {
+            Direction::Right => S4Direction::East,
+            Direction::Down => S4Direction::South,
+            Direction::Left => S4Direction::West,
+            Direction::Up => S4Direction::North,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Command {
     direction: Direction,
     delta: isize,
 }
 
-#[aoc_generator(day18, part1)]
-pub fn part1(src: &str) -> Vec<Command> {
-    src.lines()
-        .map(|l| {
-            let mut iter = l.split_whitespace();
-            let direction = iter.next().unwrap().parse().unwrap();
-            let delta = iter.next().unwrap().parse().unwrap();
-            Command { direction, delta }
+impl Command {
+    #[inline]
+    pub fn vector(&self) -> GridVector {
+        S4Direction::from(self.direction).vector(self.delta)
+    }
+}
+
+/// A line of the dig plan under both readings: `plain` from the direction
+/// letter and length, and `hex` from the code that the first part mistakes
+/// for a colour.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DigInstruction {
+    pub plain: Command,
+    pub hex: Command,
+    pub colour: u32,
+}
+
+impl DigInstruction {
+    pub fn parse(line: &str) -> Result<Self, ParseErrorKind> {
+        use ParseErrorKind::*;
+        let mut fields = line.split_whitespace();
+        let (Some(direction), Some(length), Some(code)) =
+            (fields.next(), fields.next(), fields.next())
+        else {
+            return Err(MissingField);
+        };
+        if fields.next().is_some() {
+            return Err(TrailingField);
+        }
+        let direction = direction.parse().map_err(|_| InvalidDirection)?;
+        if length.is_empty() || !length.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(InvalidLength);
+        }
+        let delta = length.parse().map_err(|_| InvalidLength)?;
+
+        let hex = code
+            .strip_prefix("(#")
+            .and_then(|c| c.strip_suffix(')'))
+            .filter(|c| c.len() == 6 && c.bytes().all(|b| b.is_ascii_hexdigit()))
+            .ok_or(InvalidColour)?;
+        let colour = u32::from_str_radix(hex, 16).unwrap();
+        let hex_direction = FromPrimitive::from_u32(colour & 0xf).ok_or(InvalidHexDirection)?;
+        Ok(Self {
+            plain: Command { direction, delta },
+            hex: Command {
+                direction: hex_direction,
+                delta: (colour >> 4) as isize,
+            },
+            colour,
         })
-        .collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParseError {
+    /// Line number, counted from one.
+    pub line: usize,
+    pub kind: ParseErrorKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseErrorKind {
+    MissingField,
+    TrailingField,
+    InvalidDirection,
+    InvalidLength,
+    InvalidColour,
+    InvalidHexDirection,
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let reason = match self.kind {
+            ParseErrorKind::MissingField => "expected a direction, a length and a colour",
+            ParseErrorKind::TrailingField => "unexpected field after the colour",
+            ParseErrorKind::InvalidDirection => "direction is not one of R, D, L or U",
+            ParseErrorKind::InvalidLength => "length is not a number",
+            ParseErrorKind::InvalidColour => "colour is not of the form (#rrggbb)",
+            ParseErrorKind::InvalidHexDirection => "last digit of the colour is not 0 to 3",
+        };
+        write!(f, "line {}: {}", self.line, reason)
+    }
 }
 
-#[aoc_generator(day18, part2)]
-pub fn part2(src: &str) -> Vec<Command> {
+impl std::error::Error for ParseError {}
+
+pub fn parse_dig_plan(src: &str) -> Result<Vec<DigInstruction>, ParseError> {
     src.lines()
-        .map(|l| {
-            let code = l.split_whitespace().skip(2).next().unwrap();
-            let delta = isize::from_str_radix(&code[2..7], 16).unwrap();
-            let direction = FromPrimitive::from_u8(code[7..].as_bytes()[0] - '0' as u8).unwrap();
-            Command { direction, delta }
-        })
+        .enumerate()
+        .map(|(i, l)| DigInstruction::parse(l).map_err(|kind| ParseError { line: i + 1, kind }))
         .collect()
 }
 
+#[aoc_generator(day18)]
+pub fn generator(src: &str) -> Vec<DigInstruction> {
+    parse_dig_plan(src).unwrap_or_else(|e| panic!("{}", e))
+}
+
 #[aoc(day18, part1)]
+pub fn part1(input: &[DigInstruction]) -> usize {
+    solve(input, |i| i.plain)
+}
+
 #[aoc(day18, part2)]
-pub fn solve(input: &[Command]) -> usize {
-    (input
-        .into_iter()
-        .fold((0, 0), |(area, y), Command { direction, delta }| {
-            use Direction::*;
-            let determinant = match direction {
-                Left => -2 * y * delta,
-                Right => 2 * y * delta,
-                _ => 0,
-            };
-            let delta_y = match direction {
-                Up => *delta,
-                Down => -*delta,
-                _ => 0,
-            };
-            (area + determinant + delta, y + delta_y)
+pub fn part2(input: &[DigInstruction]) -> usize {
+    solve(input, |i| i.hex)
+}
+
+/// Lagoon size when every instruction is read through `projection`.
+pub fn solve<F: Fn(&DigInstruction) -> Command>(input: &[DigInstruction], projection: F) -> usize {
+    trench(input.iter().map(projection)).lattice_points()
+}
+
+fn trench<I: IntoIterator<Item = Command>>(commands: I) -> Polygon {
+    Polygon::from_walk((0, 0), commands.into_iter().map(|c| c.vector()))
+}
+
+/// One straight run of the trench, with the colour it is painted in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Segment {
+    pub direction: S4Direction,
+    pub length: isize,
+    pub colour: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DigPlan {
+    segments: Vec<Segment>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrenchError {
+    /// The trench ends at `end` instead of back at the start.
+    NotClosed { end: LatticePoint },
+    /// Segments `first` and `second` touch or cross.
+    SelfIntersecting { first: usize, second: usize },
+}
+
+impl Display for TrenchError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TrenchError::NotClosed { end } => {
+                write!(f, "trench ends at {:?} instead of its start", end)
+            }
+            TrenchError::SelfIntersecting { first, second } => {
+                write!(f, "segments {} and {} of the trench meet", first, second)
+            }
+        }
+    }
+}
+
+impl std::error::Error for TrenchError {}
+
+impl DigPlan {
+    /// Builds the plan from each instruction read through `projection`,
+    /// keeping its colour.
+    pub fn from_instructions<F>(input: &[DigInstruction], projection: F) -> Self
+    where
+        F: Fn(&DigInstruction) -> Command,
+    {
+        let segments = input
+            .iter()
+            .map(|instruction| {
+                let Command { direction, delta } = projection(instruction);
+                Segment {
+                    direction: direction.into(),
+                    length: delta,
+                    colour: instruction.colour,
+                }
+            })
+            .collect();
+        Self { segments }
+    }
+
+    #[inline]
+    pub fn segments(&self) -> &[Segment] {
+        &self.segments
+    }
+
+    /// Corners of the trench, starting from `(0, 0)`.
+    fn corners(&self) -> impl Iterator<Item = LatticePoint> + '_ {
+        self.segments.iter().scan((0, 0), |(i, j), segment| {
+            let (di, dj) = segment.direction.vector(segment.length);
+            (*i, *j) = (*i + di, *j + dj);
+            Some((*i, *j))
+        })
+    }
+
+    pub fn trench(&self) -> Polygon {
+        Polygon::from_walk(
+            (0, 0),
+            self.segments.iter().map(|s| s.direction.vector(s.length)),
+        )
+    }
+
+    /// Checks that the trench ends where it started and never meets itself
+    /// elsewhere than at the corners joining consecutive segments.
+    pub fn validate(&self) -> Result<(), TrenchError> {
+        let end = self.corners().last().unwrap_or((0, 0));
+        if end != (0, 0) {
+            return Err(TrenchError::NotClosed { end });
+        }
+        match self.trench().self_intersections().first() {
+            Some(&(first, second)) => Err(TrenchError::SelfIntersecting { first, second }),
+            None => Ok(()),
+        }
+    }
+
+    /// Smallest and largest row and column reached by the trench.
+    pub fn bounds(&self) -> (LatticePoint, LatticePoint) {
+        self.corners().fold(((0, 0), (0, 0)), |(min, max), (i, j)| {
+            ((min.0.min(i), min.1.min(j)), (max.0.max(i), max.1.max(j)))
         })
-        .0
-        / 2
-        + 1) as usize
+    }
+
+    /// Draws the trench with `#` over `.` terrain, shrinking it by a whole
+    /// factor until it fits in `max_size` rows and columns.
+    pub fn render_text(&self, max_size: usize) -> String {
+        let ((min_i, min_j), (max_i, max_j)) = self.bounds();
+        let extent = (max_i - min_i).max(max_j - min_j) as usize + 1;
+        let scale = extent.div_ceil(max_size).max(1) as isize;
+        let rows = ((max_i - min_i) / scale + 1) as usize;
+        let cols = ((max_j - min_j) / scale + 1) as usize;
+        let mut dug = Grid::new(rows, cols);
+        let scaled = |(i, j): LatticePoint| {
+            (
+                ((i - min_i) / scale) as usize,
+                ((j - min_j) / scale) as usize,
+            )
+        };
+        let mut from = (0, 0);
+        for to in self.corners() {
+            let (i0, j0) = scaled(from);
+            let (i1, j1) = scaled(to);
+            for i in i0.min(i1)..=i0.max(i1) {
+                for j in j0.min(j1)..=j0.max(j1) {
+                    dug[(i, j)] = true;
+                }
+            }
+            from = to;
+        }
+        let mut out = String::with_capacity(rows * (cols + 1));
+        for row in dug.iter_rows() {
+            out.extend(row.map(|&d| if d { '#' } else { '.' }));
+            out.push('\n');
+        }
+        out
+    }
+
+    /// Draws each segment as a line in its own colour, in a view box fitted to
+    /// the trench.
+    pub fn render_svg(&self) -> String {
+        let ((min_i, min_j), (max_i, max_j)) = self.bounds();
+        let extent = (max_i - min_i).max(max_j - min_j).max(1);
+        let stroke_width = (extent / 200).max(1);
+        let margin = stroke_width;
+        let mut out = String::new();
+        writeln!(
+            out,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="{} {} {} {}">"#,
+            min_j - margin,
+            min_i - margin,
+            max_j - min_j + 2 * margin,
+            max_i - min_i + 2 * margin
+        )
+        .unwrap();
+        let mut from = (0, 0);
+        for (segment, to) in self.segments.iter().zip(self.corners()) {
+            writeln!(
+                out,
+                r##"  <line x1="{}" y1="{}" x2="{}" y2="{}" stroke="#{:06x}" stroke-width="{}" stroke-linecap="square"/>"##,
+                from.1, from.0, to.1, to.0, segment.colour, stroke_width
+            )
+            .unwrap();
+            from = to;
+        }
+        out.push_str("</svg>\n");
+        out
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use itertools::Itertools;
+
     use super::*;
 
     const SAMPLE: &'static str = r"R 6 (#70c710)
@@ -102,6 +357,139 @@ U 2 (#7a21e3)";
 
     #[test]
     fn part1_sample() {
-        assert_eq!(solve(&part1(SAMPLE)), 62);
+        assert_eq!(part1(&generator(SAMPLE)), 62);
+    }
+
+    #[test]
+    fn part2_sample() {
+        assert_eq!(part2(&generator(SAMPLE)), 952408144115);
+    }
+
+    #[test]
+    fn trench_sample() {
+        let trench = trench(generator(SAMPLE).iter().map(|i| i.plain));
+        assert!(trench.is_rectilinear());
+        assert!(trench.is_simple());
+        assert_eq!(trench.boundary_points(), 38);
+        assert_eq!(trench.interior_points(), 24);
+    }
+
+    #[test]
+    fn dig_plan_sample() {
+        let plan = DigPlan::from_instructions(&generator(SAMPLE), |i| i.plain);
+        assert_eq!(
+            plan.segments()[0],
+            Segment {
+                direction: S4Direction::East,
+                length: 6,
+                colour: 0x70c710
+            }
+        );
+        assert_eq!(plan.validate(), Ok(()));
+        assert_eq!(plan.trench().lattice_points(), 62);
+        assert_eq!(
+            plan.render_text(20),
+            r"#######
+#.....#
+###...#
+..#...#
+..#...#
+###.###
+#...#..
+##..###
+.#....#
+.######
+"
+        );
+
+        let plan = DigPlan::from_instructions(&generator(SAMPLE), |i| i.hex);
+        assert_eq!(plan.segments()[0].length, 461937);
+        assert_eq!(plan.segments()[0].colour, 0x70c710);
+        assert_eq!(plan.validate(), Ok(()));
+        assert_eq!(plan.trench().lattice_points(), 952408144115);
+        let text = plan.render_text(40);
+        assert!(text.lines().count() <= 40);
+        assert!(text.lines().all(|l| l.len() <= 40));
+    }
+
+    #[test]
+    fn dig_plan_validation() {
+        let open = SAMPLE.lines().take(13).join("\n");
+        assert_eq!(
+            DigPlan::from_instructions(&generator(&open), |i| i.plain).validate(),
+            Err(TrenchError::NotClosed { end: (2, 0) })
+        );
+        let crossing = "R 2 (#000000)
+D 2 (#000000)
+L 1 (#000000)
+U 3 (#000000)
+L 1 (#000000)
+D 1 (#000000)";
+        let error = DigPlan::from_instructions(&generator(crossing), |i| i.plain)
+            .validate()
+            .unwrap_err();
+        assert_eq!(
+            error,
+            TrenchError::SelfIntersecting {
+                first: 0,
+                second: 3
+            }
+        );
+        assert_eq!(error.to_string(), "segments 0 and 3 of the trench meet");
+    }
+
+    #[test]
+    fn render_svg_sample() {
+        let svg = DigPlan::from_instructions(&generator(SAMPLE), |i| i.plain).render_svg();
+        assert!(svg.starts_with(r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="-1 -1 8 11">"#));
+        assert_eq!(svg.matches("<line ").count(), 14);
+        assert!(svg.contains(r##"<line x1="0" y1="0" x2="6" y2="0" stroke="#70c710""##));
+        assert!(svg.contains(r##"stroke="#015232""##));
+    }
+
+    #[test]
+    fn dig_instruction_parsing() {
+        assert_eq!(
+            DigInstruction::parse("R 6 (#70c710)"),
+            Ok(DigInstruction {
+                plain: Command {
+                    direction: Direction::Right,
+                    delta: 6
+                },
+                hex: Command {
+                    direction: Direction::Right,
+                    delta: 461937
+                },
+                colour: 0x70c710
+            })
+        );
+        use ParseErrorKind::*;
+        for (line, kind) in [
+            ("R 6", MissingField),
+            ("R 6 (#70c710) x", TrailingField),
+            ("X 6 (#70c710)", InvalidDirection),
+            ("R -6 (#70c710)", InvalidLength),
+            ("R six (#70c710)", InvalidLength),
+            ("R 6 (70c710)", InvalidColour),
+            ("R 6 (#70c71)", InvalidColour),
+            ("R 6 (#70c71g)", InvalidColour),
+            ("R 6 (#+0c710)", InvalidColour),
+            ("R 6 #70c710", InvalidColour),
+            ("R 6 (#70c714)", InvalidHexDirection),
+        ] {
+            assert_eq!(DigInstruction::parse(line), Err(kind), "{}", line);
+        }
+        let error = parse_dig_plan("R 6 (#70c710)\nD 5 (#0dc57f)").unwrap_err();
+        assert_eq!(
+            error,
+            ParseError {
+                line: 2,
+                kind: InvalidHexDirection
+            }
+        );
+        assert_eq!(
+            error.to_string(),
+            "line 2: last digit of the colour is not 0 to 3"
+        );
     }
 }
@@ -1,8 +1,14 @@
-use std::{cmp::Ordering, collections::HashMap, ops::RangeInclusive};
+use std::{
+    cmp::Ordering,
+    collections::HashMap,
+    fmt::{self, Write},
+    io::{self, BufRead},
+    ops::RangeInclusive,
+};
 
 use aoc_runner_derive::aoc;
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Attribute {
     X,
     M,
@@ -133,35 +139,27 @@ impl Workflow<'_> {
             .unwrap_or(&self.default)
     }
 
-    fn apply_over<'a>(
-        &'a self,
-        mut part_range: Part<RangeInclusive<usize>>,
-        accepted_ranges: &mut Vec<PartRange>,
-        remaining_ranges: &mut Vec<(&'a str, PartRange)>,
-    ) {
+    /// Splits `part_range` along the rules, handing each piece to `emit`
+    /// together with the action it ends up at, in rule order.
+    fn apply_over<'a, F>(&'a self, mut part_range: PartRange, mut emit: F)
+    where
+        F: FnMut(PartRange, &'a Action<'a>),
+    {
         for rule in &self.rules {
             let (applicable, remainder) = rule.apply_over(part_range);
-            match applicable {
-                Some((part_range, Action::Accept)) => accepted_ranges.push(part_range),
-                Some((part_range, Action::Workflow(workflow))) => {
-                    remaining_ranges.push((workflow, part_range))
-                }
-                _ => {}
+            if let Some((part_range, action)) = applicable {
+                emit(part_range, action);
             }
             match remainder {
                 Some(remainder) => part_range = remainder,
                 None => return,
             }
         }
-        match self.default {
-            Action::Workflow(workflow) => remaining_ranges.push((workflow, part_range)),
-            Action::Accept => accepted_ranges.push(part_range),
-            Action::Reject => {}
-        }
+        emit(part_range, &self.default);
     }
 }
 
-#[derive(Debug, Default, Clone)]
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
 pub struct Part<T> {
     x: T,
     m: T,
@@ -211,6 +209,10 @@ impl<T> Part<T> {
 }
 
 impl Part<usize> {
+    pub fn new(x: usize, m: usize, a: usize, s: usize) -> Self {
+        Self { x, m, a, s }
+    }
+
     pub fn total_rating(&self) -> usize {
         self.x + self.m + self.a + self.s
     }
@@ -228,62 +230,354 @@ impl PartRange {
 
 #[aoc(day19, part1)]
 pub fn part1(input: &str) -> usize {
-    let mut lines = input.lines();
-    let workflows = lines
-        .by_ref()
-        .take_while(|l| l.len() > 0)
-        .map(|l| {
-            let mut iter = l.split(&['{', '}']);
-            let name = iter.next().unwrap();
-            let workflow = Workflow::from(iter.next().unwrap());
-            (name, workflow)
-        })
-        .collect::<HashMap<_, _>>();
-    let parts = lines.map(|l| Part::from(&l[1..l.len() - 1]));
+    let (workflows, parts) = split_input(input);
+    let engine = Engine::parse(workflows);
+    parts
+        .lines()
+        .map(parse_part)
+        .filter(|part| engine.accepts(part))
+        .map(|p| p.total_rating())
+        .sum()
+}
 
+#[aoc(day19, part1, decision_tree)]
+pub fn part1_decision_tree(input: &str) -> usize {
+    let (workflows, parts) = split_input(input);
+    let tree = Engine::parse(workflows).decision_tree();
     parts
-        .filter(|part| {
-            let mut current_workflow = &workflows["in"];
-            loop {
-                match current_workflow.apply(part) {
-                    Action::Accept => break true,
-                    Action::Reject => break false,
-                    Action::Workflow(new_workflow) => current_workflow = &workflows[new_workflow],
+        .lines()
+        .map(parse_part)
+        .filter(|part| tree.accepts(part))
+        .map(|p| p.total_rating())
+        .sum()
+}
+
+/// Splits the input into its workflows and its parts.
+pub fn split_input(input: &str) -> (&str, &str) {
+    input.split_once("\n\n").unwrap_or((input.trim_end(), ""))
+}
+
+/// Parses a workflow written as `name{rule,...,default}`.
+pub fn parse_workflow(line: &str) -> (&str, Workflow<'_>) {
+    let mut iter = line.split(&['{', '}']);
+    let name = iter.next().unwrap();
+    (name, Workflow::from(iter.next().unwrap()))
+}
+
+/// Parses every workflow, keyed by name.
+pub fn parse_workflows(workflows: &str) -> HashMap<&str, Workflow<'_>> {
+    workflows.lines().map(parse_workflow).collect()
+}
+
+/// Parses a part written as `{x=..,m=..,a=..,s=..}`.
+pub fn parse_part(line: &str) -> Part<usize> {
+    Part::from(
+        line.trim()
+            .strip_prefix('{')
+            .and_then(|l| l.strip_suffix('}'))
+            .unwrap(),
+    )
+}
+
+/// Reads parts one line at a time, skipping blank lines.
+pub fn parse_parts<R: BufRead>(reader: R) -> impl Iterator<Item = io::Result<Part<usize>>> {
+    reader
+        .lines()
+        .filter(|l| l.as_ref().map_or(true, |l| !l.trim().is_empty()))
+        .map(|l| l.map(|l| parse_part(&l)))
+}
+
+/// Where a rule sends a part once workflow names are resolved.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Target {
+    Workflow(usize),
+    Accept,
+    Reject,
+}
+
+#[derive(Debug, Clone)]
+struct CompiledRule {
+    attr: Attribute,
+    condition: Ordering,
+    value: usize,
+    next: Target,
+}
+
+impl CompiledRule {
+    #[inline]
+    fn matches(&self, part: &Part<usize>) -> bool {
+        part.attr(self.attr).cmp(&self.value) == self.condition
+    }
+}
+
+#[derive(Debug, Clone)]
+struct CompiledWorkflow {
+    rules: Vec<CompiledRule>,
+    default: Target,
+}
+
+/// Outcome of rating one part.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Verdict<'s> {
+    pub accepted: bool,
+    /// Names of the workflows visited, starting with `in`.
+    pub trace: Vec<&'s str>,
+}
+
+/// Workflows parsed once, with every workflow name resolved to an index.
+#[derive(Debug, Clone)]
+pub struct Engine<'s> {
+    names: Vec<&'s str>,
+    workflows: Vec<CompiledWorkflow>,
+    start: usize,
+}
+
+impl<'s> Engine<'s> {
+    pub fn parse(workflows: &'s str) -> Self {
+        let parsed = workflows.lines().map(parse_workflow).collect::<Vec<_>>();
+        let indices = parsed
+            .iter()
+            .enumerate()
+            .map(|(i, (name, _))| (*name, i))
+            .collect::<HashMap<_, _>>();
+        let resolve = |action: &Action| match action {
+            Action::Workflow(name) => Target::Workflow(
+                *indices
+                    .get(name)
+                    .unwrap_or_else(|| panic!("undefined workflow {}", name)),
+            ),
+            Action::Accept => Target::Accept,
+            Action::Reject => Target::Reject,
+        };
+        let workflows = parsed
+            .iter()
+            .map(|(_, workflow)| CompiledWorkflow {
+                rules: workflow
+                    .rules
+                    .iter()
+                    .map(|rule| CompiledRule {
+                        attr: rule.attr,
+                        condition: rule.condition,
+                        value: rule.value,
+                        next: resolve(&rule.next),
+                    })
+                    .collect(),
+                default: resolve(&workflow.default),
+            })
+            .collect();
+        Self {
+            names: parsed.iter().map(|(name, _)| *name).collect(),
+            workflows,
+            start: indices["in"],
+        }
+    }
+
+    #[inline]
+    fn step(&self, workflow: usize, part: &Part<usize>) -> Target {
+        let workflow = &self.workflows[workflow];
+        workflow
+            .rules
+            .iter()
+            .find(|rule| rule.matches(part))
+            .map_or(workflow.default, |rule| rule.next)
+    }
+
+    pub fn accepts(&self, part: &Part<usize>) -> bool {
+        let mut current = self.start;
+        loop {
+            match self.step(current, part) {
+                Target::Workflow(next) => current = next,
+                Target::Accept => return true,
+                Target::Reject => return false,
+            }
+        }
+    }
+
+    pub fn rate(&self, part: &Part<usize>) -> Verdict<'s> {
+        let mut current = self.start;
+        let mut trace = vec![self.names[current]];
+        loop {
+            match self.step(current, part) {
+                Target::Workflow(next) => {
+                    current = next;
+                    trace.push(self.names[current]);
+                }
+                Target::Accept => {
+                    return Verdict {
+                        accepted: true,
+                        trace,
+                    }
+                }
+                Target::Reject => {
+                    return Verdict {
+                        accepted: false,
+                        trace,
+                    }
                 }
             }
+        }
+    }
+
+    /// Rates every part as it comes.
+    pub fn rate_all<'e, I>(
+        &'e self,
+        parts: I,
+    ) -> impl Iterator<Item = (Part<usize>, Verdict<'s>)> + 'e
+    where
+        I: IntoIterator<Item = Part<usize>>,
+        I::IntoIter: 'e,
+    {
+        parts.into_iter().map(move |part| {
+            let verdict = self.rate(&part);
+            (part, verdict)
         })
-        .map(|p| p.total_rating())
-        .sum()
+    }
+
+    /// Flattens the workflows into a single tree of threshold tests, leaving
+    /// out every test whose outcome is already settled by the ones above it.
+    pub fn decision_tree(&self) -> DecisionTree {
+        let mut tree = DecisionTree {
+            nodes: vec![DecisionNode::Leaf(false), DecisionNode::Leaf(true)],
+            root: REJECT_NODE,
+        };
+        tree.root = self.build(&mut tree, self.start, 0, full_range());
+        tree
+    }
+
+    fn build(
+        &self,
+        tree: &mut DecisionTree,
+        workflow: usize,
+        rule: usize,
+        range: PartRange,
+    ) -> usize {
+        let Some(current) = self.workflows[workflow].rules.get(rule) else {
+            return self.build_target(tree, self.workflows[workflow].default, range);
+        };
+        let (threshold, then_below) = match current.condition {
+            Ordering::Less => (current.value, true),
+            Ordering::Greater => (current.value + 1, false),
+            _ => panic!(),
+        };
+        let attr_range = range.attr(current.attr).clone();
+        let branch = |tree: &mut DecisionTree, below: bool, range: PartRange| {
+            if below == then_below {
+                self.build_target(tree, current.next, range)
+            } else {
+                self.build(tree, workflow, rule + 1, range)
+            }
+        };
+        if *attr_range.end() < threshold {
+            return branch(tree, true, range);
+        }
+        if *attr_range.start() >= threshold {
+            return branch(tree, false, range);
+        }
+        let below = branch(
+            tree,
+            true,
+            range
+                .clone()
+                .with_attr(current.attr, *attr_range.start()..=threshold - 1),
+        );
+        let at_or_above = branch(
+            tree,
+            false,
+            range.with_attr(current.attr, threshold..=*attr_range.end()),
+        );
+        if below == at_or_above {
+            return below;
+        }
+        tree.nodes.push(DecisionNode::Split {
+            attr: current.attr,
+            threshold,
+            below,
+            at_or_above,
+        });
+        tree.nodes.len() - 1
+    }
+
+    fn build_target(&self, tree: &mut DecisionTree, target: Target, range: PartRange) -> usize {
+        match target {
+            Target::Workflow(next) => self.build(tree, next, 0, range),
+            Target::Reject => REJECT_NODE,
+            Target::Accept => ACCEPT_NODE,
+        }
+    }
+}
+
+const REJECT_NODE: usize = 0;
+const ACCEPT_NODE: usize = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DecisionNode {
+    Leaf(bool),
+    /// Goes to `below` if the attribute is less than `threshold`.
+    Split {
+        attr: Attribute,
+        threshold: usize,
+        below: usize,
+        at_or_above: usize,
+    },
+}
+
+/// Binary tree of threshold tests equivalent to a set of workflows.
+#[derive(Debug, Clone)]
+pub struct DecisionTree {
+    nodes: Vec<DecisionNode>,
+    root: usize,
+}
+
+impl DecisionTree {
+    pub fn accepts(&self, part: &Part<usize>) -> bool {
+        let mut current = self.root;
+        loop {
+            match self.nodes[current] {
+                DecisionNode::Leaf(accepted) => return accepted,
+                DecisionNode::Split {
+                    attr,
+                    threshold,
+                    below,
+                    at_or_above,
+                } => {
+                    current = if *part.attr(attr) < threshold {
+                        below
+                    } else {
+                        at_or_above
+                    }
+                }
+            }
+        }
+    }
+
+    /// Classifies a batch of parts, in order.
+    pub fn classify(&self, parts: &[Part<usize>]) -> Vec<bool> {
+        parts.iter().map(|part| self.accepts(part)).collect()
+    }
+}
+
+fn full_range() -> PartRange {
+    const INITIAL_RANGE: RangeInclusive<usize> = 1..=4000;
+    Part {
+        x: INITIAL_RANGE,
+        m: INITIAL_RANGE,
+        a: INITIAL_RANGE,
+        s: INITIAL_RANGE,
+    }
 }
 
 #[aoc(day19, part2)]
 pub fn part2(input: &str) -> usize {
-    const INITIAL_RANGE: RangeInclusive<usize> = 1..=4000;
+    let workflows = parse_workflows(split_input(input).0);
 
-    let mut lines = input.lines();
-    let workflows = lines
-        .by_ref()
-        .take_while(|l| l.len() > 0)
-        .map(|l| {
-            let mut iter = l.split(&['{', '}']);
-            let name = iter.next().unwrap();
-            let workflow = Workflow::from(iter.next().unwrap());
-            (name, workflow)
-        })
-        .collect::<HashMap<_, _>>();
-
-    let mut part_ranges = vec![(
-        "in",
-        Part {
-            x: INITIAL_RANGE,
-            a: INITIAL_RANGE,
-            m: INITIAL_RANGE,
-            s: INITIAL_RANGE,
-        },
-    )];
+    let mut part_ranges = vec![("in", full_range())];
     let mut accepted_ranges = Vec::new();
     while let Some((workflow, part_range)) = part_ranges.pop() {
-        workflows[&workflow].apply_over(part_range, &mut accepted_ranges, &mut part_ranges);
+        workflows[&workflow].apply_over(part_range, |part_range, action| match action {
+            Action::Accept => accepted_ranges.push(part_range),
+            Action::Workflow(workflow) => part_ranges.push((workflow, part_range)),
+            Action::Reject => {}
+        });
     }
     accepted_ranges
         .into_iter()
@@ -291,9 +585,155 @@ pub fn part2(input: &str) -> usize {
         .sum()
 }
 
+impl fmt::Display for PartRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{{x={}..={},m={}..={},a={}..={},s={}..={}}}",
+            self.x.start(),
+            self.x.end(),
+            self.m.start(),
+            self.m.end(),
+            self.a.start(),
+            self.a.end(),
+            self.s.start(),
+            self.s.end()
+        )
+    }
+}
+
+/// Ranges of parts as they are split by the workflows, starting from every
+/// possible part at `in`. Each workflow node knows how many parts its subtree
+/// accepts: the volumes of its children add up to it, and the root holds the
+/// part 2 total.
+#[derive(Debug, Clone)]
+pub enum RangeTree<'s> {
+    Workflow {
+        name: &'s str,
+        range: PartRange,
+        accepted: usize,
+        children: Vec<RangeTree<'s>>,
+    },
+    Accept(PartRange),
+    Reject(PartRange),
+}
+
+impl<'s> RangeTree<'s> {
+    pub fn new(workflows: &'s HashMap<&'s str, Workflow<'s>>) -> Self {
+        Self::expand(workflows, "in", full_range())
+    }
+
+    fn expand(
+        workflows: &'s HashMap<&'s str, Workflow<'s>>,
+        name: &'s str,
+        range: PartRange,
+    ) -> Self {
+        let mut children = Vec::new();
+        workflows[name].apply_over(range.clone(), |part_range, action| {
+            children.push(match action {
+                Action::Accept => RangeTree::Accept(part_range),
+                Action::Reject => RangeTree::Reject(part_range),
+                Action::Workflow(next) => Self::expand(workflows, next, part_range),
+            })
+        });
+        let accepted = children.iter().map(RangeTree::accepted).sum();
+        RangeTree::Workflow {
+            name,
+            range,
+            accepted,
+            children,
+        }
+    }
+
+    pub fn range(&self) -> &PartRange {
+        match self {
+            RangeTree::Workflow { range, .. } => range,
+            RangeTree::Accept(range) | RangeTree::Reject(range) => range,
+        }
+    }
+
+    /// Number of parts accepted within this subtree.
+    pub fn accepted(&self) -> usize {
+        match self {
+            RangeTree::Workflow { accepted, .. } => *accepted,
+            RangeTree::Accept(range) => range.total_accepted(),
+            RangeTree::Reject(_) => 0,
+        }
+    }
+
+    fn label(&self) -> &str {
+        match self {
+            RangeTree::Workflow { name, .. } => name,
+            RangeTree::Accept(_) => "A",
+            RangeTree::Reject(_) => "R",
+        }
+    }
+
+    fn children(&self) -> &[RangeTree<'s>] {
+        match self {
+            RangeTree::Workflow { children, .. } => children,
+            _ => &[],
+        }
+    }
+
+    /// One node per line, indented by depth: label, range and accepted volume.
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        let mut stack = vec![(0, self)];
+        while let Some((depth, node)) = stack.pop() {
+            writeln!(
+                text,
+                "{:indent$}{} {}: {}",
+                "",
+                node.label(),
+                node.range(),
+                node.accepted(),
+                indent = 2 * depth
+            )
+            .unwrap();
+            stack.extend(node.children().iter().rev().map(|child| (depth + 1, child)));
+        }
+        text
+    }
+
+    /// Graphviz digraph of the tree, with accepting leaves in green and
+    /// rejecting ones in grey.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph day19 {\n    node [shape=box];\n");
+        let mut stack = vec![(None, self)];
+        let mut next_id = 0;
+        while let Some((parent, node)) = stack.pop() {
+            let id = next_id;
+            next_id += 1;
+            let style = match node {
+                RangeTree::Workflow { .. } => "",
+                RangeTree::Accept(_) => ", style=filled, fillcolor=palegreen",
+                RangeTree::Reject(_) => ", style=filled, fillcolor=lightgrey",
+            };
+            writeln!(
+                dot,
+                "    n{} [label=\"{}\\n{}\\naccepted {}\"{}];",
+                id,
+                node.label(),
+                node.range(),
+                node.accepted(),
+                style
+            )
+            .unwrap();
+            if let Some(parent) = parent {
+                writeln!(dot, "    n{} -> n{};", parent, id).unwrap();
+            }
+            stack.extend(node.children().iter().rev().map(|child| (Some(id), child)));
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::utils::test_rng::XorShift;
 
     const SAMPLE: &'static str = r"px{a<2006:qkq,m>2090:A,rfg}
 pv{a>1716:R,A}
@@ -322,4 +762,67 @@ hdj{m>838:A,pv}
     pub fn part2_sample() {
         assert_eq!(part2(SAMPLE), 167409079868000);
     }
+
+    #[test]
+    pub fn engine_traces() {
+        let (workflows, parts) = split_input(SAMPLE);
+        let engine = Engine::parse(workflows);
+        let verdicts = engine
+            .rate_all(parse_parts(parts.as_bytes()).map(Result::unwrap))
+            .map(|(_, verdict)| verdict)
+            .collect::<Vec<_>>();
+        let expected = [
+            (true, vec!["in", "qqz", "qs", "lnx"]),
+            (false, vec!["in", "px", "rfg", "gd"]),
+            (true, vec!["in", "qqz", "hdj", "pv"]),
+            (false, vec!["in", "px", "qkq", "crn"]),
+            (true, vec!["in", "px", "rfg"]),
+        ];
+        assert_eq!(verdicts.len(), expected.len());
+        for (verdict, (accepted, trace)) in verdicts.into_iter().zip(expected) {
+            assert_eq!(verdict, Verdict { accepted, trace });
+        }
+    }
+
+    #[test]
+    pub fn decision_tree_matches_engine() {
+        assert_eq!(part1_decision_tree(SAMPLE), 19114);
+        let engine = Engine::parse(split_input(SAMPLE).0);
+        let tree = engine.decision_tree();
+        let mut rng = XorShift::default();
+        let mut next = move || 1 + rng.below(4000);
+        let parts = (0..100_000)
+            .map(|_| Part::new(next(), next(), next(), next()))
+            .collect::<Vec<_>>();
+        let classified = tree.classify(&parts);
+        for (part, accepted) in parts.iter().zip(classified) {
+            assert_eq!(accepted, engine.accepts(part), "{:?}", part);
+        }
+    }
+
+    #[test]
+    pub fn range_tree_sample() {
+        let workflows = parse_workflows(split_input(SAMPLE).0);
+        let tree = RangeTree::new(&workflows);
+        assert_eq!(tree.accepted(), part2(SAMPLE));
+        let text = tree.to_text();
+        let lines = text.lines().collect::<Vec<_>>();
+        assert_eq!(
+            lines[..4],
+            [
+                "in {x=1..=4000,m=1..=4000,a=1..=4000,s=1..=4000}: 167409079868000",
+                "  px {x=1..=4000,m=1..=4000,a=1..=4000,s=1..=1350}: 58664554428000",
+                "    qkq {x=1..=4000,m=1..=4000,a=1..=2005,s=1..=1350}: 29806731000000",
+                "      A {x=1..=1415,m=1..=4000,a=1..=2005,s=1..=1350}: 15320205000000",
+            ]
+        );
+        let leaves = lines
+            .iter()
+            .filter(|l| l.trim_start().starts_with("A "))
+            .count();
+        assert_eq!(leaves, 9);
+        let dot = tree.to_dot();
+        assert!(dot.starts_with("digraph day19 {\n"));
+        assert_eq!(dot.matches(" -> ").count(), lines.len() - 1);
+    }
 }
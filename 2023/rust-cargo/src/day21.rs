@@ -7,10 +7,12 @@ use num::integer::Roots;
 
 use crate::utils::{
     grid::{
+        bit_grid::BitGrid,
         grid_translate, grid_vector_add, parse_grid_with_u8,
         traversal::{grid_bfs, Cursor, GridBfsOptions},
         GridCoords, GridVector, ALL_S4_DIRECTIONS,
     },
+    polynomial::NewtonPolynomial,
     set::Set,
 };
 
@@ -58,11 +60,7 @@ fn part1_generic<const N: usize>((map, start): &Input) -> usize {
 
     impl<const N: usize> Set<&DistanceCursor<N>> for GridSet {
         fn insert(&mut self, value: &DistanceCursor<N>) -> bool {
-            if self.0[value.0] {
-                return false;
-            }
-            self.0[value.0] = true;
-            true
+            self.0.insert(value.0)
         }
     }
 
@@ -74,11 +72,33 @@ fn part1_generic<const N: usize>((map, start): &Input) -> usize {
         |acc, &DistanceCursor(_, d)| *acc += (d % 2 == parity) as usize,
         GridBfsOptions::with_capacity_and_visited_set(
             2 * (map.rows() + map.cols()),
-            GridSet(Grid::new(map.rows(), map.cols())),
+            GridSet(BitGrid::new(map.rows(), map.cols())),
         ),
     )
 }
 
+#[aoc(day21, part1, bitwise)]
+pub fn part1_bitwise(input: &Input) -> usize {
+    part1_bitwise_generic::<64>(input)
+}
+
+fn part1_bitwise_generic<const N: usize>((map, start): &Input) -> usize {
+    let garden = !BitGrid::from(map);
+    let mut frontier = BitGrid::new(map.rows(), map.cols());
+    frontier.set(*start, true);
+    for _ in 0..N {
+        let mut next = BitGrid::new(map.rows(), map.cols());
+        for direction in ALL_S4_DIRECTIONS {
+            let mut shifted = frontier.clone();
+            shifted.shift(direction, 1);
+            next |= &shifted;
+        }
+        next &= &garden;
+        frontier = next;
+    }
+    frontier.count_ones()
+}
+
 // #[aoc(day21, part2, naive)]
 pub fn part2_naive(input: &Input) -> usize {
     part2_naive_impl::<26501365>(input)
@@ -132,6 +152,84 @@ fn part2_naive_impl<const N: usize>((map, start): &Input) -> usize {
     )
 }
 
+#[aoc(day21, part2, polynomial)]
+pub fn part2_polynomial(input: &Input) -> usize {
+    plots_by_extrapolation(input, 26501365, 1)
+}
+
+/// Once the reachable plots span whole copies of the map, their count grows
+/// quadratically with each further map width walked. This fits that quadratic
+/// to three samples, the first taken `warmup_tiles` map widths in, and
+/// extrapolates it to `steps`.
+fn plots_by_extrapolation((map, start): &Input, steps: usize, warmup_tiles: usize) -> usize {
+    #[derive(Debug, PartialEq, Eq, Clone, Copy)]
+    struct LimitedCursor {
+        coords: GridVector,
+        distance: usize,
+        limit: usize,
+    }
+
+    impl Cursor<bool> for LimitedCursor {
+        type Neighbors<'s> = ArrayVec<Self, 4>
+        where
+            Self: 's;
+
+        fn neighbors<'s: 'd, 'd>(&'s self, grid: &'d Grid<bool>) -> Self::Neighbors<'d> {
+            if self.distance >= self.limit {
+                return std::iter::empty().collect();
+            }
+            ALL_S4_DIRECTIONS
+                .into_iter()
+                .map(|d| grid_vector_add(self.coords, d.vector(1)))
+                .filter(|&(i, j)| {
+                    !grid[(
+                        i.rem_euclid(grid.rows() as isize) as usize,
+                        j.rem_euclid(grid.cols() as isize) as usize,
+                    )]
+                })
+                .map(|coords| Self {
+                    coords,
+                    distance: self.distance + 1,
+                    limit: self.limit,
+                })
+                .collect()
+        }
+    }
+
+    struct LimitedCursorSet(HashSet<GridVector>);
+
+    impl Set<&LimitedCursor> for LimitedCursorSet {
+        fn insert(&mut self, value: &LimitedCursor) -> bool {
+            self.0.insert(value.coords)
+        }
+    }
+
+    let period = map.rows();
+    let base = steps % period + warmup_tiles * period;
+    let samples = [base, base + period, base + 2 * period];
+    let distances = grid_bfs(
+        map,
+        LimitedCursor {
+            coords: (start.0 as isize, start.1 as isize),
+            distance: 0,
+            limit: samples[2],
+        },
+        Vec::new(),
+        |distances, cursor| distances.push(cursor.distance),
+        GridBfsOptions::with_capacity_and_visited_set(
+            4 * samples[2],
+            LimitedCursorSet(HashSet::new()),
+        ),
+    );
+    let counts = samples.map(|s| {
+        distances
+            .iter()
+            .filter(|&&d| d <= s && d % 2 == s % 2)
+            .count() as i128
+    });
+    NewtonPolynomial::fit(counts).eval(((steps - base) / period) as i128) as usize
+}
+
 #[aoc(day21, part2, pattern)]
 pub fn part2_pattern((map, start): &Input) -> usize {
     // This implementation uses the following additional properties observed in the input
@@ -166,11 +264,7 @@ pub fn part2_pattern((map, start): &Input) -> usize {
 
     impl Set<&DistanceCursor> for GridSet {
         fn insert(&mut self, value: &DistanceCursor) -> bool {
-            if self.0[value.0] {
-                return false;
-            }
-            self.0[value.0] = true;
-            true
+            self.0.insert(value.0)
         }
     }
 
@@ -181,7 +275,7 @@ pub fn part2_pattern((map, start): &Input) -> usize {
         |distances, &DistanceCursor(coords, d)| distances[coords] = Some(d),
         GridBfsOptions::with_capacity_and_visited_set(
             2 * (map.rows() + map.cols()),
-            GridSet(Grid::new(map.rows(), map.cols())),
+            GridSet(BitGrid::new(map.rows(), map.cols())),
         ),
     );
 
@@ -208,7 +302,7 @@ pub fn part2_pattern((map, start): &Input) -> usize {
     ((N + 1) * (N + 1)) * odd_full + (N * N) * even_full - (N + 1) * odd_corners + N * even_corners
 }
 
-struct GridSet(Grid<bool>);
+struct GridSet(BitGrid);
 
 #[cfg(test)]
 mod tests {
@@ -235,6 +329,11 @@ mod tests {
         assert_eq!(part1_generic::<6>(&sample()), 16);
     }
 
+    #[test]
+    pub fn part1_bitwise_sample() {
+        assert_eq!(part1_bitwise_generic::<6>(&sample()), 16);
+    }
+
     #[test]
     pub fn part2_naive_sample() {
         assert_eq!(part2_naive_impl::<6>(&sample()), 16);
@@ -245,4 +344,11 @@ mod tests {
         assert_eq!(part2_naive_impl::<1000>(&sample()), 668697);
         assert_eq!(part2_naive_impl::<5000>(&sample()), 16733044);
     }
+
+    #[test]
+    pub fn plots_by_extrapolation_sample() {
+        assert_eq!(plots_by_extrapolation(&sample(), 500, 3), 167004);
+        assert_eq!(plots_by_extrapolation(&sample(), 1000, 3), 668697);
+        assert_eq!(plots_by_extrapolation(&sample(), 5000, 3), 16733044);
+    }
 }
@@ -4,7 +4,10 @@ use aoc_runner_derive::aoc;
 use grid::Grid;
 use petgraph::visit::EdgeRef;
 
-use crate::utils::grid::{grid_translate, parse_grid_with_u8, S4Direction, ALL_S4_DIRECTIONS};
+use crate::utils::{
+    grid::{bit_grid::BitGrid, grid_translate, parse_grid_with_u8, S4Direction, ALL_S4_DIRECTIONS},
+    set::Set,
+};
 
 #[aoc(day23, part1)]
 pub fn part1(input: &str) -> usize {
@@ -135,7 +138,7 @@ pub fn part2(input: &str) -> usize {
         let mut node_map = Grid::new(dimensions.0, dimensions.1);
         node_map[start_point] = Some(graph.add_node(()));
         node_map[end_point] = Some(graph.add_node(()));
-        let mut visited = Grid::new(dimensions.0, dimensions.1);
+        let mut visited = BitGrid::new(dimensions.0, dimensions.1);
         let mut stack = Vec::with_capacity(dimensions.0);
         stack.push((start_point, 0, node_map[start_point].unwrap()));
         while let Some((current, mut distance, mut src)) = stack.pop() {
@@ -156,10 +159,9 @@ pub fn part2(input: &str) -> usize {
                 src = dest;
             }
 
-            if visited[current] {
+            if !visited.insert(current) {
                 continue;
             }
-            visited[current] = true;
             stack.extend(neighbors.map(|n| (n, distance + 1, src)));
         }
         graph
@@ -0,0 +1,194 @@
+use std::{collections::HashMap, hash::Hash};
+
+/// Shape of the sequence `x0, f(x0), f(f(x0)), ...` once it starts repeating:
+/// the first `prefix` states are never revisited, and every state after them
+/// repeats every `length` steps.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cycle {
+    pub prefix: usize,
+    pub length: usize,
+}
+
+impl Cycle {
+    /// Returns the smallest step count that reaches the same state as `steps`.
+    #[inline]
+    pub fn reduce(&self, steps: usize) -> usize {
+        if steps < self.prefix {
+            steps
+        } else {
+            self.prefix + (steps - self.prefix) % self.length
+        }
+    }
+}
+
+/// Finds the cycle by remembering every visited state, so it takes as many
+/// steps as the prefix and cycle combined, at the cost of storing them all.
+pub fn find_cycle<S, F>(initial: S, mut step: F) -> Cycle
+where
+    S: Clone + Eq + Hash,
+    F: FnMut(&S) -> S,
+{
+    let mut seen = HashMap::new();
+    let mut current = initial;
+    let mut i = 0;
+    loop {
+        if let Some(&previous) = seen.get(&current) {
+            return Cycle {
+                prefix: previous,
+                length: i - previous,
+            };
+        }
+        let next = step(&current);
+        seen.insert(current, i);
+        current = next;
+        i += 1;
+    }
+}
+
+/// Finds the cycle with Brent's algorithm, holding at most two states at once.
+///
+/// States are compared directly, so unlike fingerprinting there are no false
+/// positives, but it steps through the sequence about three times as often as
+/// [`find_cycle`].
+pub fn find_cycle_brent<S, F>(initial: S, mut step: F) -> Cycle
+where
+    S: Clone + Eq,
+    F: FnMut(&S) -> S,
+{
+    let (_, _, length) = brent_hare(&initial, &mut step, usize::MAX);
+    let length = length.unwrap();
+
+    let mut tortoise = initial.clone();
+    let mut hare = initial;
+    for _ in 0..length {
+        hare = step(&hare);
+    }
+    let mut prefix = 0;
+    while tortoise != hare {
+        tortoise = step(&tortoise);
+        hare = step(&hare);
+        prefix += 1;
+    }
+    Cycle { prefix, length }
+}
+
+/// Returns the state reached after applying `step` `steps` times, skipping
+/// whole cycles. The sequence must eventually repeat.
+///
+/// Only the first phase of Brent's algorithm is needed: once the hare is known
+/// to be on the cycle, it keeps going from there for the remaining steps modulo
+/// the cycle length, so the prefix is never walked twice.
+pub fn state_after<S, F>(initial: S, mut step: F, steps: usize) -> S
+where
+    S: Clone + Eq,
+    F: FnMut(&S) -> S,
+{
+    if steps == 0 {
+        return initial;
+    }
+    let (mut hare, taken, length) = brent_hare(&initial, &mut step, steps);
+    if let Some(length) = length {
+        for _ in 0..(steps - taken) % length {
+            hare = step(&hare);
+        }
+    }
+    hare
+}
+
+/// First phase of Brent's algorithm: moves a hare from `initial` until it meets
+/// the tortoise, which waits at every power of two, or until it has taken
+/// `limit` steps. Returns the hare, the steps it took and, if they met, the
+/// cycle length.
+fn brent_hare<S, F>(initial: &S, step: &mut F, limit: usize) -> (S, usize, Option<usize>)
+where
+    S: Clone + Eq,
+    F: FnMut(&S) -> S,
+{
+    let mut power = 1;
+    let mut length = 1;
+    let mut tortoise = initial.clone();
+    let mut hare = step(initial);
+    let mut taken = 1;
+    while tortoise != hare {
+        if taken == limit {
+            return (hare, taken, None);
+        }
+        if power == length {
+            tortoise = hare.clone();
+            power *= 2;
+            length = 0;
+        }
+        hare = step(&hare);
+        taken += 1;
+        length += 1;
+    }
+    (hare, taken, Some(length))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Counts up to `prefix`, then loops over the next `length` values.
+    fn rho(prefix: usize, length: usize) -> impl FnMut(&usize) -> usize {
+        move |&x| {
+            if x + 1 < prefix + length {
+                x + 1
+            } else {
+                prefix
+            }
+        }
+    }
+
+    const SHAPES: [(usize, usize); 5] = [(0, 1), (0, 5), (3, 4), (7, 1), (10, 3)];
+
+    #[test]
+    fn find_cycle_shapes() {
+        for (prefix, length) in SHAPES {
+            let expected = Cycle { prefix, length };
+            assert_eq!(find_cycle(0, rho(prefix, length)), expected);
+            assert_eq!(find_cycle_brent(0, rho(prefix, length)), expected);
+        }
+    }
+
+    #[test]
+    fn reduce() {
+        let cycle = Cycle {
+            prefix: 3,
+            length: 4,
+        };
+        assert_eq!(cycle.reduce(2), 2);
+        assert_eq!(cycle.reduce(3), 3);
+        assert_eq!(cycle.reduce(10), 6);
+        assert_eq!(cycle.reduce(1_000_000_003), 3);
+        let fixed_point = Cycle {
+            prefix: 0,
+            length: 1,
+        };
+        assert_eq!(fixed_point.reduce(42), 0);
+    }
+
+    #[test]
+    fn state_after_matches_iteration() {
+        for (prefix, length) in SHAPES {
+            let mut step = rho(prefix, length);
+            let mut state = 0;
+            for steps in 0..40 {
+                assert_eq!(
+                    state_after(0, rho(prefix, length), steps),
+                    state,
+                    "{} steps with prefix {} and length {}",
+                    steps,
+                    prefix,
+                    length
+                );
+                state = step(&state);
+            }
+            let cycle = Cycle { prefix, length };
+            assert_eq!(
+                state_after(0, rho(prefix, length), 1_000_000_000),
+                cycle.reduce(1_000_000_000)
+            );
+        }
+    }
+}
@@ -0,0 +1,199 @@
+use num::Integer;
+
+use crate::utils::grid::GridVector;
+
+/// Point of the integer lattice, in the same `(row, column)` convention as [`GridVector`].
+pub type LatticePoint = (isize, isize);
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Orientation {
+    Clockwise,
+    CounterClockwise,
+    Degenerate,
+}
+
+/// Closed polygon whose vertices lie on the integer lattice.
+///
+/// The last vertex is implicitly connected back to the first. Orientations are
+/// reported as seen on screen, with rows growing downwards.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Polygon {
+    vertices: Vec<LatticePoint>,
+}
+
+impl Polygon {
+    pub fn from_vertices(vertices: Vec<LatticePoint>) -> Self {
+        Self { vertices }
+    }
+
+    /// Builds a polygon by walking from `start` along each vector in turn. If the
+    /// walk ends back at `start`, the repeated vertex is dropped.
+    pub fn from_walk<I: IntoIterator<Item = GridVector>>(start: LatticePoint, walk: I) -> Self {
+        let mut vertices = vec![start];
+        let mut current = start;
+        for (di, dj) in walk {
+            current = (current.0 + di, current.1 + dj);
+            vertices.push(current);
+        }
+        if vertices.len() > 1 && vertices.last() == Some(&start) {
+            vertices.pop();
+        }
+        Self { vertices }
+    }
+
+    #[inline]
+    pub fn vertices(&self) -> &[LatticePoint] {
+        &self.vertices
+    }
+
+    pub fn edges(&self) -> impl Iterator<Item = (LatticePoint, LatticePoint)> + '_ {
+        let n = self.vertices.len();
+        (0..n).map(move |k| (self.vertices[k], self.vertices[(k + 1) % n]))
+    }
+
+    /// Twice the signed area given by the shoelace formula, which is always an
+    /// integer. Positive when counter-clockwise.
+    pub fn twice_signed_area(&self) -> isize {
+        self.edges()
+            .map(|((i0, j0), (i1, j1))| i0 * j1 - i1 * j0)
+            .sum()
+    }
+
+    /// Area of the polygon, rounded down when it is not a whole number.
+    pub fn area(&self) -> usize {
+        self.twice_signed_area().unsigned_abs() / 2
+    }
+
+    pub fn orientation(&self) -> Orientation {
+        use std::cmp::Ordering::*;
+        match self.twice_signed_area().cmp(&0) {
+            Greater => Orientation::CounterClockwise,
+            Less => Orientation::Clockwise,
+            Equal => Orientation::Degenerate,
+        }
+    }
+
+    /// Number of lattice points lying on the edges of the polygon.
+    pub fn boundary_points(&self) -> usize {
+        self.edges()
+            .map(|((i0, j0), (i1, j1))| (i1 - i0).unsigned_abs().gcd(&(j1 - j0).unsigned_abs()))
+            .sum()
+    }
+
+    /// Number of lattice points strictly inside the polygon, by Pick's theorem.
+    /// Only meaningful for simple polygons; degenerate walks that retrace their
+    /// own edges have no interior and give 0.
+    pub fn interior_points(&self) -> usize {
+        (self.twice_signed_area().unsigned_abs() + 2).saturating_sub(self.boundary_points()) / 2
+    }
+
+    /// Number of lattice points inside or on the polygon.
+    pub fn lattice_points(&self) -> usize {
+        self.interior_points() + self.boundary_points()
+    }
+
+    pub fn is_rectilinear(&self) -> bool {
+        self.edges().all(|(a, b)| a.0 == b.0 || a.1 == b.1)
+    }
+
+    /// Returns the indices of every pair of edges that touch or cross each
+    /// other, except for consecutive edges meeting at their shared vertex.
+    /// Edge `k` goes from vertex `k` to vertex `k + 1`.
+    pub fn self_intersections(&self) -> Vec<(usize, usize)> {
+        let n = self.vertices.len();
+        let edges = self.edges().collect::<Vec<_>>();
+        let mut intersections = Vec::new();
+        for k in 0..n {
+            for l in k + 1..n {
+                let (a0, a1) = edges[k];
+                let (b0, b1) = edges[l];
+                let intersects = if l == k + 1 {
+                    folds_back(a0, a1, b1)
+                } else if k == 0 && l == n - 1 {
+                    folds_back(b0, b1, a1)
+                } else {
+                    segments_intersect((a0, a1), (b0, b1))
+                };
+                if intersects {
+                    intersections.push((k, l));
+                }
+            }
+        }
+        intersections
+    }
+
+    pub fn is_simple(&self) -> bool {
+        self.vertices.len() >= 3 && self.self_intersections().is_empty()
+    }
+}
+
+#[inline]
+fn cross(o: LatticePoint, a: LatticePoint, b: LatticePoint) -> isize {
+    (a.0 - o.0) * (b.1 - o.1) - (a.1 - o.1) * (b.0 - o.0)
+}
+
+/// Whether `p`, known to be collinear with `a` and `b`, lies between them.
+#[inline]
+fn within(p: LatticePoint, a: LatticePoint, b: LatticePoint) -> bool {
+    a.0.min(b.0) <= p.0 && p.0 <= a.0.max(b.0) && a.1.min(b.1) <= p.1 && p.1 <= a.1.max(b.1)
+}
+
+/// Whether the path `a -> b -> c` turns back over itself at `b`.
+fn folds_back(a: LatticePoint, b: LatticePoint, c: LatticePoint) -> bool {
+    cross(a, b, c) == 0 && (b.0 - a.0) * (c.0 - b.0) + (b.1 - a.1) * (c.1 - b.1) < 0
+}
+
+fn segments_intersect(
+    (a0, a1): (LatticePoint, LatticePoint),
+    (b0, b1): (LatticePoint, LatticePoint),
+) -> bool {
+    let d0 = cross(b0, b1, a0);
+    let d1 = cross(b0, b1, a1);
+    let d2 = cross(a0, a1, b0);
+    let d3 = cross(a0, a1, b1);
+    if d0.signum() * d1.signum() < 0 && d2.signum() * d3.signum() < 0 {
+        return true;
+    }
+    (d0 == 0 && within(a0, b0, b1))
+        || (d1 == 0 && within(a1, b0, b1))
+        || (d2 == 0 && within(b0, a0, a1))
+        || (d3 == 0 && within(b1, a0, a1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn orientation() {
+        let square = Polygon::from_vertices(vec![(0, 0), (0, 1), (1, 1), (1, 0)]);
+        assert_eq!(square.orientation(), Orientation::Clockwise);
+        let mut vertices = square.vertices().to_vec();
+        vertices.reverse();
+        assert_eq!(
+            Polygon::from_vertices(vertices).orientation(),
+            Orientation::CounterClockwise
+        );
+        let line = Polygon::from_vertices(vec![(0, 0), (0, 1), (0, 2)]);
+        assert_eq!(line.orientation(), Orientation::Degenerate);
+    }
+
+    #[test]
+    fn self_intersections() {
+        let square = Polygon::from_walk((0, 0), [(0, 2), (2, 0), (0, -2), (-2, 0)]);
+        assert!(square.self_intersections().is_empty());
+        assert!(square.is_simple());
+        let bowtie = Polygon::from_vertices(vec![(0, 0), (0, 2), (2, 0), (2, 2)]);
+        assert_eq!(bowtie.self_intersections(), [(1, 3)]);
+        assert!(!bowtie.is_simple());
+    }
+
+    #[test]
+    fn degenerate_walk() {
+        let folded = Polygon::from_walk((0, 0), [(0, 2), (0, -2)]);
+        assert_eq!(folded.self_intersections(), [(0, 1)]);
+        assert_eq!(folded.area(), 0);
+        assert_eq!(folded.boundary_points(), 4);
+        assert_eq!(folded.interior_points(), 0);
+    }
+}
@@ -0,0 +1,258 @@
+use std::ops::{BitAndAssign, BitOrAssign, Index, Not};
+
+use bitvec::{slice::BitSlice, vec::BitVec};
+use grid::Grid;
+
+use crate::utils::{
+    grid::{GridCoords, GridDimensions, S4Direction},
+    set::Set,
+};
+
+/// Bit-packed alternative to [`Grid<bool>`], stored row-major in a single [`BitVec`].
+///
+/// Rows are contiguous, so whole-row and whole-grid operations (AND, OR, shifts,
+/// popcount) run a machine word at a time instead of a byte per cell.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BitGrid {
+    bits: BitVec,
+    rows: usize,
+    cols: usize,
+}
+
+impl BitGrid {
+    pub fn new(rows: usize, cols: usize) -> Self {
+        Self {
+            bits: BitVec::repeat(false, rows * cols),
+            rows,
+            cols,
+        }
+    }
+
+    #[inline]
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    #[inline]
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+
+    #[inline]
+    pub fn dimensions(&self) -> GridDimensions {
+        (self.rows, self.cols)
+    }
+
+    #[inline]
+    pub fn get(&self, (i, j): GridCoords) -> Option<bool> {
+        (i < self.rows && j < self.cols).then(|| self.bits[i * self.cols + j])
+    }
+
+    #[inline]
+    pub fn set(&mut self, coords: GridCoords, value: bool) {
+        let index = self.index_of(coords);
+        self.bits.set(index, value);
+    }
+
+    /// Sets the cell at `coords` and returns its previous value.
+    #[inline]
+    pub fn replace(&mut self, coords: GridCoords, value: bool) -> bool {
+        let index = self.index_of(coords);
+        self.bits.replace(index, value)
+    }
+
+    #[inline]
+    pub fn row(&self, i: usize) -> &BitSlice {
+        &self.bits[i * self.cols..(i + 1) * self.cols]
+    }
+
+    #[inline]
+    pub fn row_mut(&mut self, i: usize) -> &mut BitSlice {
+        &mut self.bits[i * self.cols..(i + 1) * self.cols]
+    }
+
+    #[inline]
+    pub fn and_row(&mut self, i: usize, other: &BitSlice) {
+        *self.row_mut(i) &= other;
+    }
+
+    #[inline]
+    pub fn or_row(&mut self, i: usize, other: &BitSlice) {
+        *self.row_mut(i) |= other;
+    }
+
+    /// Shifts row `i` by `by` cells towards `direction`, which must be either
+    /// [`S4Direction::West`] or [`S4Direction::East`]. Cells shifted past the
+    /// edge are discarded and vacated cells are cleared.
+    pub fn shift_row(&mut self, i: usize, direction: S4Direction, by: usize) {
+        let row = self.row_mut(i);
+        if by >= row.len() {
+            row.fill(false);
+            return;
+        }
+        match direction {
+            S4Direction::West => row.shift_start(by),
+            S4Direction::East => row.shift_end(by),
+            d => panic!("Cannot shift a row towards {:?}", d),
+        }
+    }
+
+    /// Shifts every cell of the grid by `by` cells towards `direction`. Cells
+    /// shifted past the edge are discarded and vacated cells are cleared.
+    pub fn shift(&mut self, direction: S4Direction, by: usize) {
+        match direction {
+            S4Direction::West | S4Direction::East => {
+                for i in 0..self.rows {
+                    self.shift_row(i, direction, by);
+                }
+            }
+            S4Direction::North | S4Direction::South if by >= self.rows => self.bits.fill(false),
+            S4Direction::North => self.bits.shift_start(by * self.cols),
+            S4Direction::South => self.bits.shift_end(by * self.cols),
+        }
+    }
+
+    #[inline]
+    pub fn count_ones(&self) -> usize {
+        self.bits.count_ones()
+    }
+
+    #[inline]
+    pub fn count_row_ones(&self, i: usize) -> usize {
+        self.row(i).count_ones()
+    }
+
+    pub fn iter_ones(&self) -> impl Iterator<Item = GridCoords> + '_ {
+        self.bits
+            .iter_ones()
+            .map(|index| (index / self.cols, index % self.cols))
+    }
+
+    #[inline]
+    fn index_of(&self, (i, j): GridCoords) -> usize {
+        assert!(
+            i < self.rows && j < self.cols,
+            "({}, {}) is out of bounds for a {}x{} grid",
+            i,
+            j,
+            self.rows,
+            self.cols
+        );
+        i * self.cols + j
+    }
+}
+
+impl Index<GridCoords> for BitGrid {
+    type Output = bool;
+
+    #[inline]
+    fn index(&self, coords: GridCoords) -> &Self::Output {
+        &self.bits[self.index_of(coords)]
+    }
+}
+
+impl From<&Grid<bool>> for BitGrid {
+    fn from(grid: &Grid<bool>) -> Self {
+        let mut bit_grid = Self::new(grid.rows(), grid.cols());
+        for (coords, &value) in grid.indexed_iter() {
+            bit_grid.set(coords, value);
+        }
+        bit_grid
+    }
+}
+
+impl BitAndAssign<&BitGrid> for BitGrid {
+    fn bitand_assign(&mut self, rhs: &BitGrid) {
+        assert_eq!(self.dimensions(), rhs.dimensions());
+        self.bits &= rhs.bits.as_bitslice();
+    }
+}
+
+impl BitOrAssign<&BitGrid> for BitGrid {
+    fn bitor_assign(&mut self, rhs: &BitGrid) {
+        assert_eq!(self.dimensions(), rhs.dimensions());
+        self.bits |= rhs.bits.as_bitslice();
+    }
+}
+
+impl Not for BitGrid {
+    type Output = BitGrid;
+
+    fn not(mut self) -> Self::Output {
+        self.bits = !self.bits;
+        self
+    }
+}
+
+impl Set<GridCoords> for BitGrid {
+    fn insert(&mut self, value: GridCoords) -> bool {
+        !self.replace(value, true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(rows: &[&str]) -> BitGrid {
+        let mut grid = BitGrid::new(rows.len(), rows[0].len());
+        for (i, row) in rows.iter().enumerate() {
+            for (j, c) in row.chars().enumerate() {
+                grid.set((i, j), c == '#');
+            }
+        }
+        grid
+    }
+
+    #[test]
+    fn shift_each_direction() {
+        let grid = parse(&["#..#", ".##.", "...#"]);
+        let expected = [
+            (S4Direction::North, [".##.", "...#", "...."]),
+            (S4Direction::South, ["....", "#..#", ".##."]),
+            (S4Direction::West, ["..#.", "##..", "..#."]),
+            (S4Direction::East, [".#..", "..##", "...."]),
+        ];
+        for (direction, rows) in expected {
+            let mut shifted = grid.clone();
+            shifted.shift(direction, 1);
+            assert_eq!(shifted, parse(&rows), "{:?}", direction);
+        }
+        let mut cleared = grid.clone();
+        cleared.shift(S4Direction::South, 3);
+        assert_eq!(cleared.count_ones(), 0);
+    }
+
+    #[test]
+    fn shift_row() {
+        let mut grid = parse(&["##.#.", "#...#"]);
+        grid.shift_row(0, S4Direction::East, 2);
+        assert_eq!(grid, parse(&["..##.", "#...#"]));
+        grid.shift_row(1, S4Direction::West, 4);
+        assert_eq!(grid, parse(&["..##.", "#...."]));
+        grid.shift_row(1, S4Direction::West, 5);
+        assert_eq!(grid, parse(&["..##.", "....."]));
+    }
+
+    #[test]
+    fn row_operations() {
+        let mut grid = parse(&["##..", "#.#."]);
+        let mask = parse(&[".##."]);
+        grid.and_row(0, mask.row(0));
+        grid.or_row(1, mask.row(0));
+        assert_eq!(grid, parse(&[".#..", "###."]));
+        assert_eq!(grid.count_row_ones(1), 3);
+    }
+
+    #[test]
+    fn not_and_iter_ones() {
+        let grid = parse(&["#..", ".#."]);
+        assert_eq!(grid.iter_ones().collect::<Vec<_>>(), [(0, 0), (1, 1)]);
+        let inverted = !grid;
+        assert_eq!(inverted, parse(&[".##", "#.#"]));
+        assert_eq!(
+            inverted.iter_ones().collect::<Vec<_>>(),
+            [(0, 1), (0, 2), (1, 0), (1, 2)]
+        );
+    }
+}
@@ -0,0 +1,110 @@
+use grid::Grid;
+
+use crate::utils::grid::{a_star::Cursor, GridDimensions};
+
+/// Cursor whose states can be numbered densely, so that a search can keep its
+/// scores in flat arrays instead of hash maps.
+pub trait DenseCursor<T>: Cursor<T> {
+    /// Upper bound on the index of any cursor reachable in the same search.
+    fn index_bound(&self, dimensions: &GridDimensions) -> usize;
+    fn index(&self, dimensions: &GridDimensions) -> usize;
+}
+
+/// Dijkstra's algorithm over dense cursor indices, using Dial's bucket queue.
+///
+/// Meant for small integer costs like digits in a grid: every cost between the
+/// cheapest and the most expensive pending cursor gets a bucket. The heuristic
+/// of the cursor is ignored.
+pub fn grid_dial<T, C, I, E>(grid: &Grid<T>, start_cursors: I, end_fn: E) -> usize
+where
+    C: DenseCursor<T>,
+    I: IntoIterator<Item = C>,
+    E: Fn(&C) -> bool,
+{
+    let dimensions = (grid.rows(), grid.cols());
+    let mut start_cursors = start_cursors.into_iter().peekable();
+    let bound = start_cursors
+        .peek()
+        .expect("no start cursors")
+        .index_bound(&dimensions);
+    let mut g_scores = vec![usize::MAX; bound];
+    let mut queue = BucketQueue::new();
+    for cursor in start_cursors {
+        let g_score = cursor.c(grid);
+        let index = cursor.index(&dimensions);
+        if g_score < g_scores[index] {
+            g_scores[index] = g_score;
+            queue.push(g_score, cursor);
+        }
+    }
+    while let Some((g_score, current)) = queue.pop() {
+        if g_score > g_scores[current.index(&dimensions)] {
+            continue;
+        }
+        if end_fn(&current) {
+            return g_score;
+        }
+        for neighbor in current.neighbors(&dimensions) {
+            let tentative_g_score = g_score + neighbor.c(grid);
+            let index = neighbor.index(&dimensions);
+            if tentative_g_score < g_scores[index] {
+                g_scores[index] = tentative_g_score;
+                queue.push(tentative_g_score, neighbor);
+            }
+        }
+    }
+    unreachable!()
+}
+
+/// Monotone priority queue: items can only be pushed with a priority at least
+/// that of the last one popped. Buckets form a ring indexed by priority modulo
+/// its size, which doubles whenever a priority falls beyond it.
+struct BucketQueue<C> {
+    buckets: Vec<Vec<C>>,
+    current: usize,
+    len: usize,
+}
+
+impl<C> BucketQueue<C> {
+    fn new() -> Self {
+        Self {
+            buckets: (0..16).map(|_| Vec::new()).collect(),
+            current: 0,
+            len: 0,
+        }
+    }
+
+    fn push(&mut self, priority: usize, item: C) {
+        debug_assert!(priority >= self.current);
+        if priority - self.current >= self.buckets.len() {
+            self.grow((priority - self.current + 1).next_power_of_two());
+        }
+        let size = self.buckets.len();
+        self.buckets[priority % size].push(item);
+        self.len += 1;
+    }
+
+    fn pop(&mut self) -> Option<(usize, C)> {
+        if self.len == 0 {
+            return None;
+        }
+        let size = self.buckets.len();
+        loop {
+            if let Some(item) = self.buckets[self.current % size].pop() {
+                self.len -= 1;
+                return Some((self.current, item));
+            }
+            self.current += 1;
+        }
+    }
+
+    fn grow(&mut self, size: usize) {
+        let old_size = self.buckets.len();
+        let mut buckets = (0..size).map(|_| Vec::new()).collect::<Vec<_>>();
+        for offset in 0..old_size {
+            let priority = self.current + offset;
+            buckets[priority % size] = std::mem::take(&mut self.buckets[priority % old_size]);
+        }
+        self.buckets = buckets;
+    }
+}
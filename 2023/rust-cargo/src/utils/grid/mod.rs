@@ -5,6 +5,9 @@ use itertools::Itertools;
 use num_derive::FromPrimitive;
 
 pub mod a_star;
+pub mod bit_grid;
+pub mod dial;
+pub mod region;
 pub mod traversal;
 
 pub type GridDimensions = (usize, usize);
@@ -0,0 +1,299 @@
+use arrayvec::ArrayVec;
+use grid::Grid;
+use itertools::Itertools;
+
+use crate::utils::{
+    grid::{
+        bit_grid::BitGrid,
+        grid_translate,
+        traversal::{grid_bfs, Cursor, GridBfsOptions},
+        GridCoords, GridVector,
+    },
+    set::Set,
+};
+
+/// Which neighbors of a cell are considered adjacent to it.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Connectivity {
+    /// Orthogonal neighbors only.
+    Four,
+    /// Orthogonal and diagonal neighbors.
+    Eight,
+}
+
+const S4_VECTORS: [GridVector; 4] = [(0, -1), (-1, 0), (0, 1), (1, 0)];
+
+const S8_VECTORS: [GridVector; 8] = [
+    (0, -1),
+    (-1, -1),
+    (-1, 0),
+    (-1, 1),
+    (0, 1),
+    (1, 1),
+    (1, 0),
+    (1, -1),
+];
+
+impl Connectivity {
+    #[inline]
+    pub fn vectors(self) -> &'static [GridVector] {
+        match self {
+            Connectivity::Four => &S4_VECTORS,
+            Connectivity::Eight => &S8_VECTORS,
+        }
+    }
+}
+
+/// Summary of a connected region: its cell count and inclusive bounding box.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Region {
+    pub size: usize,
+    pub min: GridCoords,
+    pub max: GridCoords,
+}
+
+impl Region {
+    fn new(coords: GridCoords) -> Self {
+        Self {
+            size: 0,
+            min: coords,
+            max: coords,
+        }
+    }
+
+    fn extend(&mut self, (i, j): GridCoords) {
+        self.size += 1;
+        self.min = (self.min.0.min(i), self.min.1.min(j));
+        self.max = (self.max.0.max(i), self.max.1.max(j));
+    }
+}
+
+/// Result of [`label_components`]: `labels[coords]` indexes into `regions`.
+#[derive(Debug, Clone)]
+pub struct Labelling {
+    pub labels: Grid<usize>,
+    pub regions: Vec<Region>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+struct RegionCursor {
+    coords: GridCoords,
+    connectivity: Connectivity,
+}
+
+impl RegionCursor {
+    fn new(coords: GridCoords, connectivity: Connectivity) -> Self {
+        Self {
+            coords,
+            connectivity,
+        }
+    }
+}
+
+impl<T: PartialEq> Cursor<T> for RegionCursor {
+    type Neighbors<'s> = ArrayVec<Self, 8>
+    where
+        Self: 's,
+        T: 's;
+
+    fn neighbors<'s: 'd, 'd>(&'s self, grid: &'d Grid<T>) -> Self::Neighbors<'d> {
+        let dimensions = (grid.rows(), grid.cols());
+        let value = &grid[self.coords];
+        self.connectivity
+            .vectors()
+            .iter()
+            .filter_map(|&v| grid_translate(self.coords, v, &dimensions))
+            .filter(|&c| grid[c] == *value)
+            .map(|c| Self::new(c, self.connectivity))
+            .collect()
+    }
+}
+
+struct CellSet<'a>(&'a mut BitGrid);
+
+impl Set<&RegionCursor> for CellSet<'_> {
+    fn insert(&mut self, value: &RegionCursor) -> bool {
+        self.0.insert(value.coords)
+    }
+}
+
+struct LabelSet<'a> {
+    labels: &'a mut Grid<Option<usize>>,
+    label: usize,
+}
+
+impl Set<&RegionCursor> for LabelSet<'_> {
+    fn insert(&mut self, value: &RegionCursor) -> bool {
+        let label = &mut self.labels[value.coords];
+        if label.is_some() {
+            return false;
+        }
+        *label = Some(self.label);
+        true
+    }
+}
+
+/// Returns every cell reachable from `start` through cells holding the same value.
+pub fn flood_fill<T: PartialEq>(
+    grid: &Grid<T>,
+    start: GridCoords,
+    connectivity: Connectivity,
+) -> BitGrid {
+    let mut filled = BitGrid::new(grid.rows(), grid.cols());
+    grid_bfs(
+        grid,
+        RegionCursor::new(start, connectivity),
+        (),
+        |_, _| {},
+        GridBfsOptions::with_capacity_and_visited_set(
+            2 * (grid.rows() + grid.cols()),
+            CellSet(&mut filled),
+        ),
+    );
+    filled
+}
+
+/// Splits the grid into maximal connected regions of equal values.
+///
+/// Labels are assigned in row-major order of each region's first cell.
+pub fn label_components<T: PartialEq>(grid: &Grid<T>, connectivity: Connectivity) -> Labelling {
+    let (rows, cols) = (grid.rows(), grid.cols());
+    let mut labels = Grid::<Option<usize>>::new(rows, cols);
+    let mut regions = Vec::new();
+    for coords in (0..rows).cartesian_product(0..cols) {
+        if labels[coords].is_some() {
+            continue;
+        }
+        let region = grid_bfs(
+            grid,
+            RegionCursor::new(coords, connectivity),
+            Region::new(coords),
+            |region, cursor| region.extend(cursor.coords),
+            GridBfsOptions::with_capacity_and_visited_set(
+                2 * (rows + cols),
+                LabelSet {
+                    labels: &mut labels,
+                    label: regions.len(),
+                },
+            ),
+        );
+        regions.push(region);
+    }
+    let labels = Grid::from_vec(labels.iter().map(|l| l.unwrap()).collect(), cols);
+    Labelling { labels, regions }
+}
+
+/// Returns the cells that are not part of `boundary` and cannot reach the edge
+/// of the grid without crossing it.
+///
+/// With [`Connectivity::Eight`], the outside leaks between boundary cells that
+/// only touch diagonally.
+pub fn enclosed_cells(boundary: &Grid<bool>, connectivity: Connectivity) -> BitGrid {
+    let (rows, cols) = (boundary.rows(), boundary.cols());
+    if rows == 0 || cols == 0 {
+        return BitGrid::new(rows, cols);
+    }
+    let mut outside = BitGrid::new(rows, cols);
+    let edges = (0..cols)
+        .flat_map(|j| [(0, j), (rows - 1, j)])
+        .chain((0..rows).flat_map(|i| [(i, 0), (i, cols - 1)]));
+    for coords in edges {
+        if boundary[coords] || outside[coords] {
+            continue;
+        }
+        grid_bfs(
+            boundary,
+            RegionCursor::new(coords, connectivity),
+            (),
+            |_, _| {},
+            GridBfsOptions::with_capacity_and_visited_set(2 * (rows + cols), CellSet(&mut outside)),
+        );
+    }
+    let mut enclosed = !outside;
+    enclosed &= &!BitGrid::from(boundary);
+    enclosed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(rows: &[&str]) -> Grid<char> {
+        Grid::from_vec(rows.concat().chars().collect(), rows[0].len())
+    }
+
+    #[test]
+    fn label_components_four() {
+        let grid = parse(&["aab", "abb", "ccb"]);
+        let labelling = label_components(&grid, Connectivity::Four);
+        assert_eq!(
+            labelling.labels,
+            Grid::from_vec(vec![0, 0, 1, 0, 1, 1, 2, 2, 1], 3)
+        );
+        assert_eq!(
+            labelling.regions,
+            [
+                Region {
+                    size: 3,
+                    min: (0, 0),
+                    max: (1, 1)
+                },
+                Region {
+                    size: 4,
+                    min: (0, 1),
+                    max: (2, 2)
+                },
+                Region {
+                    size: 2,
+                    min: (2, 0),
+                    max: (2, 1)
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn label_components_eight() {
+        let grid = parse(&["a.b", ".a.", "b.a"]);
+        assert_eq!(label_components(&grid, Connectivity::Four).regions.len(), 9);
+        let labelling = label_components(&grid, Connectivity::Eight);
+        assert_eq!(
+            labelling.labels,
+            Grid::from_vec(vec![0, 1, 2, 1, 0, 1, 3, 1, 0], 3)
+        );
+        let sizes = labelling.regions.iter().map(|r| r.size).collect::<Vec<_>>();
+        assert_eq!(sizes, [3, 4, 1, 1]);
+        assert_eq!(
+            (labelling.regions[0].min, labelling.regions[0].max),
+            ((0, 0), (2, 2))
+        );
+    }
+
+    #[test]
+    fn flood_fill_same_value() {
+        let grid = parse(&["aab", "abb", "ccb"]);
+        let filled = flood_fill(&grid, (2, 2), Connectivity::Four);
+        assert_eq!(
+            filled.iter_ones().collect::<Vec<_>>(),
+            [(0, 2), (1, 1), (1, 2), (2, 2)]
+        );
+        let filled = flood_fill(&grid, (0, 0), Connectivity::Eight);
+        assert_eq!(filled.count_ones(), 3);
+    }
+
+    #[test]
+    fn enclosed_cells_diagonal_leak() {
+        let rows = [".....", "..#..", ".#.#.", "..#..", "....."];
+        let boundary = Grid::from_vec(rows.concat().chars().map(|c| c == '#').collect(), 5);
+        let enclosed = enclosed_cells(&boundary, Connectivity::Four);
+        assert_eq!(enclosed.iter_ones().collect::<Vec<_>>(), [(2, 2)]);
+        let enclosed = enclosed_cells(&boundary, Connectivity::Eight);
+        assert_eq!(enclosed.count_ones(), 0);
+    }
+
+    #[test]
+    fn enclosed_cells_empty_grid() {
+        let enclosed = enclosed_cells(&Grid::new(0, 0), Connectivity::Four);
+        assert_eq!(enclosed.dimensions(), (0, 0));
+    }
+}
@@ -0,0 +1,164 @@
+/// Returns `(g, x, y)` such that `a * x + b * y == g`, where `g` is the
+/// non-negative greatest common divisor of `a` and `b`.
+pub fn extended_gcd(a: i64, b: i64) -> (i64, i64, i64) {
+    let (mut old_r, mut r) = (a, b);
+    let (mut old_x, mut x) = (1, 0);
+    let (mut old_y, mut y) = (0, 1);
+    while r != 0 {
+        let q = old_r / r;
+        (old_r, r) = (r, old_r - q * r);
+        (old_x, x) = (x, old_x - q * x);
+        (old_y, y) = (y, old_y - q * y);
+    }
+    if old_r < 0 {
+        (-old_r, -old_x, -old_y)
+    } else {
+        (old_r, old_x, old_y)
+    }
+}
+
+/// Returns the inverse of `a` modulo `modulus` in `0..modulus`, if `a` and
+/// `modulus` are coprime.
+pub fn mod_inverse(a: i64, modulus: i64) -> Option<i64> {
+    let (g, x, _) = extended_gcd(a.rem_euclid(modulus), modulus);
+    (g == 1).then(|| x.rem_euclid(modulus))
+}
+
+/// Computes `base.pow(exp) % modulus` by repeated squaring.
+pub fn mod_pow(base: u64, mut exp: u64, modulus: u64) -> u64 {
+    assert!(modulus > 0, "modulus must be positive, got {}", modulus);
+    let modulus = modulus as u128;
+    let mut base = base as u128 % modulus;
+    let mut result = 1 % modulus;
+    while exp > 0 {
+        if exp & 1 == 1 {
+            result = result * base % modulus;
+        }
+        base = base * base % modulus;
+        exp >>= 1;
+    }
+    result as u64
+}
+
+/// The set of integers `x` such that `x ≡ residue (mod modulus)`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Congruence {
+    pub residue: i64,
+    pub modulus: i64,
+}
+
+impl Congruence {
+    /// Builds a congruence with its residue normalized into `0..modulus`.
+    pub fn new(residue: i64, modulus: i64) -> Self {
+        assert!(modulus > 0, "modulus must be positive, got {}", modulus);
+        Self {
+            residue: residue.rem_euclid(modulus),
+            modulus,
+        }
+    }
+
+    /// Returns the smallest solution that is greater than or equal to `min`.
+    pub fn first_at_or_after(&self, min: i64) -> i64 {
+        min + (self.residue - min).rem_euclid(self.modulus)
+    }
+
+    /// Combines two congruences into the one satisfied by exactly the integers
+    /// satisfying both, or `None` if no integer does or the combined modulus
+    /// does not fit in an `i64`. The moduli need not be coprime.
+    pub fn intersect(&self, other: &Congruence) -> Option<Congruence> {
+        let (g, p, _) = extended_gcd(self.modulus, other.modulus);
+        let difference = other.residue - self.residue;
+        if difference % g != 0 {
+            return None;
+        }
+        let other_reduced = (other.modulus / g) as i128;
+        let k = (difference / g) as i128 * p as i128 % other_reduced;
+        let modulus = self.modulus as i128 * other_reduced;
+        let residue = (self.residue as i128 + self.modulus as i128 * k).rem_euclid(modulus);
+        Some(Congruence {
+            residue: residue as i64,
+            modulus: i64::try_from(modulus).ok()?,
+        })
+    }
+}
+
+/// Generalized Chinese Remainder Theorem: combines every congruence into one,
+/// or returns `None` if they have no common solution or their combined modulus
+/// overflows.
+pub fn crt<I: IntoIterator<Item = Congruence>>(congruences: I) -> Option<Congruence> {
+    congruences
+        .into_iter()
+        .try_fold(Congruence::new(0, 1), |acc, c| acc.intersect(&c))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extended_gcd_identity() {
+        for (a, b, gcd) in [
+            (240, 46, 2),
+            (-12, 18, 6),
+            (12, -18, 6),
+            (0, 5, 5),
+            (7, 0, 7),
+        ] {
+            let (g, x, y) = extended_gcd(a, b);
+            assert_eq!(g, gcd, "gcd({}, {})", a, b);
+            assert_eq!(a * x + b * y, g, "gcd({}, {})", a, b);
+        }
+    }
+
+    #[test]
+    fn mod_inverse_values() {
+        assert_eq!(mod_inverse(3, 11), Some(4));
+        assert_eq!(mod_inverse(10, 17), Some(12));
+        assert_eq!(mod_inverse(-3, 11), Some(7));
+        assert_eq!(mod_inverse(6, 9), None);
+    }
+
+    #[test]
+    fn mod_pow_values() {
+        assert_eq!(mod_pow(2, 10, 1000), 24);
+        assert_eq!(mod_pow(3, 0, 7), 1);
+        assert_eq!(mod_pow(5, 3, 1), 0);
+        assert_eq!(mod_pow(2, 64, u64::MAX), 1);
+        assert_eq!(mod_pow(u64::MAX - 1, 2, u64::MAX), 1);
+    }
+
+    #[test]
+    #[should_panic]
+    fn mod_pow_zero_modulus() {
+        mod_pow(2, 3, 0);
+    }
+
+    #[test]
+    fn crt_coprime() {
+        let congruences = [
+            Congruence::new(2, 3),
+            Congruence::new(3, 5),
+            Congruence::new(2, 7),
+        ];
+        let solution = crt(congruences).unwrap();
+        assert_eq!(solution, Congruence::new(23, 105));
+        assert_eq!(solution.first_at_or_after(100), 128);
+        assert_eq!(crt([]), Some(Congruence::new(0, 1)));
+    }
+
+    #[test]
+    fn crt_non_coprime() {
+        let solution = crt([Congruence::new(2, 6), Congruence::new(8, 10)]);
+        assert_eq!(solution, Some(Congruence::new(8, 30)));
+        assert_eq!(crt([Congruence::new(1, 4), Congruence::new(2, 6)]), None);
+    }
+
+    #[test]
+    fn crt_overflow() {
+        let congruences = [
+            Congruence::new(1, 4_000_000_007),
+            Congruence::new(2, 4_000_000_009),
+        ];
+        assert_eq!(crt(congruences), None);
+    }
+}
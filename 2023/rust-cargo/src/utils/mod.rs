@@ -1,4 +1,10 @@
+pub mod cycle;
 pub mod debug;
+pub mod geometry;
 pub mod grid;
+pub mod math;
+pub mod polynomial;
 pub mod set;
+#[cfg(test)]
+pub mod test_rng;
 pub mod vec;
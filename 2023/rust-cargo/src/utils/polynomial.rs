@@ -0,0 +1,41 @@
+/// Integer-valued polynomial in Newton forward-difference form, fitted to
+/// values sampled at `x = 0, 1, 2, ...`.
+///
+/// Evaluation is exact at every integer `x`, including negative ones, as long
+/// as the intermediate values fit in an [`i128`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NewtonPolynomial {
+    /// `differences[k]` is the `k`-th forward difference at `x = 0`.
+    differences: Vec<i128>,
+}
+
+impl NewtonPolynomial {
+    /// Fits the polynomial of lowest degree through `values[x]` for each `x`.
+    pub fn fit<I: IntoIterator<Item = i128>>(values: I) -> Self {
+        let mut row = values.into_iter().collect::<Vec<_>>();
+        let mut differences = Vec::with_capacity(row.len());
+        while !row.iter().all(|&v| v == 0) {
+            differences.push(row[0]);
+            row = row.windows(2).map(|w| w[1] - w[0]).collect();
+        }
+        Self { differences }
+    }
+
+    /// Degree of the polynomial, or `None` if it is identically zero.
+    pub fn degree(&self) -> Option<usize> {
+        self.differences.len().checked_sub(1)
+    }
+
+    pub fn eval(&self, x: i128) -> i128 {
+        // binomial holds `x choose k`, which is an integer for every integer `x`.
+        let mut binomial = 1;
+        let mut value = 0;
+        for (k, d) in self.differences.iter().enumerate() {
+            if k > 0 {
+                binomial = binomial * (x - k as i128 + 1) / k as i128;
+            }
+            value += d * binomial;
+        }
+        value
+    }
+}
@@ -0,0 +1,32 @@
+/// Xorshift generator for randomized tests: fast, reproducible and with no
+/// dependencies, but not meant for anything beyond shuffling test inputs.
+#[derive(Debug, Clone)]
+pub struct XorShift {
+    state: u64,
+}
+
+impl XorShift {
+    /// `seed` must not be zero.
+    pub fn new(seed: u64) -> Self {
+        assert_ne!(seed, 0, "xorshift cannot start from zero");
+        Self { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        self.state
+    }
+
+    /// Returns a value in `0..bound`.
+    pub fn below(&mut self, bound: usize) -> usize {
+        (self.next_u64() % bound as u64) as usize
+    }
+}
+
+impl Default for XorShift {
+    fn default() -> Self {
+        Self::new(0x2545f4914f6cdd1d)
+    }
+}